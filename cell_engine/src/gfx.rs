@@ -57,6 +57,34 @@ pub fn display_cell_bordered(
         .unwrap_or_else(|e| println!("{}", e));
 }
 
+/// Render the outline of a single cell from the grid, `border_width` pixels thick
+///
+/// Translates from game space to pixel space
+pub fn display_cell_outline(
+    renderer: &mut Canvas<Window>,
+    row: u32,
+    col: u32,
+    cell: Color,
+    cell_width: u32,
+    border_width: u32,
+) {
+    let cell_height = cell_width; // All cells are square
+    let x = cell_width * col;
+    let y = cell_width * row;
+
+    renderer.set_draw_color(cell);
+    for i in 0..border_width {
+        renderer
+            .draw_rect(Rect::new(
+                (x + i) as i32,
+                (y + i) as i32,
+                cell_width - (i * 2),
+                cell_height - (i * 2),
+            ))
+            .unwrap_or_else(|e| println!("{}", e));
+    }
+}
+
 /// Render a single cell from the grid
 ///
 /// Translates from game space to pixel space
@@ -78,6 +78,17 @@ impl<T> Manager<T> {
         }
     }
 
+    /// Iterate over the entities with this component and their values, in instance order
+    pub fn iter(&self) -> impl Iterator<Item = (&Entity, &T)> {
+        self.data
+            .entity
+            .iter()
+            .zip(self.data.value.iter())
+            .enumerate()
+            .filter(move |(i, (e, _))| self.map.get(e) == Some(i))
+            .map(|(_, pair)| pair)
+    }
+
     /// Create an instance from an index to the data arrays.
     fn make_instance(i: usize) -> Instance {
         i
@@ -2,6 +2,11 @@
 //!
 use crate::component_manager::{ComponentManager, Manager};
 use entity_man::Entity;
+#[cfg(feature = "snapshot")]
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "snapshot")]
+use crate::snapshot::{self, Codec};
 
 pub trait ComponentRegistry<'a> {
     /// Registers a component type
@@ -15,6 +20,12 @@ pub trait ComponentRegistry<'a> {
     where
         T: Sync + Send + 'static;
 
+    /// Registers a component type, and saves its components in snapshots under `name`
+    #[cfg(feature = "snapshot")]
+    fn register_saved<T>(&mut self, name: &'static str)
+    where
+        T: Serialize + DeserializeOwned + Sync + Send + 'static;
+
     /// get a reference to the ComponentManager for type T
     fn get<T>(&self) -> Option<&T>
     where
@@ -52,11 +63,18 @@ type Repo = anymap::Map<dyn anymap::any::Any + Sync + Send>;
 
 pub struct Registry {
     repo: Repo,
+    /// Snapshot codecs of the component types registered with `register_saved`
+    #[cfg(feature = "snapshot")]
+    pub(crate) codecs: Vec<Codec<Registry>>,
 }
 
 impl<'a> Registry {
     pub fn new() -> Self {
-        Self { repo: Repo::new() }
+        Self {
+            repo: Repo::new(),
+            #[cfg(feature = "snapshot")]
+            codecs: Vec::new(),
+        }
     }
 }
 
@@ -83,6 +101,15 @@ impl<'a> ComponentRegistry<'a> for Registry {
         self.repo.insert(component);
     }
 
+    #[cfg(feature = "snapshot")]
+    fn register_saved<T>(&mut self, name: &'static str)
+    where
+        T: Serialize + DeserializeOwned + Sync + Send + 'static,
+    {
+        self.register2::<T>();
+        snapshot::add_codec(&mut self.codecs, snapshot::component_codec::<T>(name));
+    }
+
     fn get<T>(&self) -> Option<&T>
     where
         T: Sync + Send + 'static,
@@ -1,11 +1,12 @@
-use std::sync;
-
 use entity_man::{entity_manager::EntityManagerU32, EntityManager};
 
 use crate::{
     component_manager::{self, ComponentManager},
     component_registry::{ComponentRegistry, Registry},
     error,
+    event::EventBus,
+    query::{Fetch, Filter},
+    resource::Resources,
     types::Entity,
 };
 
@@ -15,7 +16,12 @@ pub use entity_builder::*;
 pub type Result<T> = std::result::Result<T, error::Error>;
 
 pub struct World {
-    pub component_registry: &'static sync::Mutex<Registry>,
+    /// Components of this world's entities. Each world has its own
+    pub component_registry: Registry,
+    /// Event channels. Systems send events here, and others read them
+    pub events: EventBus,
+    /// Values the world holds one of, by type
+    pub resources: Resources,
     pub entity_manager: Box<dyn EntityManager>,
 
     pub entity_list: Vec<Entity>,
@@ -24,14 +30,11 @@ pub struct World {
 impl World {
     /// Create a new instance
     pub fn new() -> Self {
-        lazy_static! {
-            pub static ref COMPONENT_REGISTRY: sync::Mutex<Registry> =
-                sync::Mutex::new(Registry::new());
-        }
-
         Self {
             entity_manager: Box::new(EntityManagerU32::new()),
-            component_registry: &COMPONENT_REGISTRY,
+            component_registry: Registry::new(),
+            events: EventBus::new(),
+            resources: Resources::new(),
             entity_list: Vec::new(),
         }
     }
@@ -56,7 +59,7 @@ impl World {
     // where
     //     T: Sync + Send + 'static,
     // {
-    //     let registry = self.component_registry.lock().unwrap();
+    //     let registry = &self.component_registry;
     //     // self.component_registry
     //     // .lock()
     //     // .unwrap()
@@ -66,6 +69,61 @@ impl World {
     //         .unwrap()
     // }
 
+    /// Add a resource, replacing the resource of the same type, if any
+    pub fn insert_resource<T>(&mut self, value: T)
+    where
+        T: Sync + Send + 'static,
+    {
+        self.resources.insert(value);
+    }
+
+    /// Get the resource of type `T`
+    /// # Panic
+    /// Panics if there's no such resource
+    pub fn resource<T>(&self) -> &T
+    where
+        T: Sync + Send + 'static,
+    {
+        self.resources
+            .get::<T>()
+            .unwrap_or_else(|| panic!("Resource not found: {}", std::any::type_name::<T>()))
+    }
+
+    /// Get the resource of type `T`
+    /// # Panic
+    /// Panics if there's no such resource
+    pub fn resource_mut<T>(&mut self) -> &mut T
+    where
+        T: Sync + Send + 'static,
+    {
+        self.resources
+            .get_mut::<T>()
+            .unwrap_or_else(|| panic!("Resource not found: {}", std::any::type_name::<T>()))
+    }
+
+    /// Iterate over the live entities that have every component in `Q`, yielding the fetched
+    /// components
+    pub fn query<'a, Q>(&'a self) -> impl Iterator<Item = Q::Item> + 'a
+    where
+        Q: Fetch<'a>,
+    {
+        self.query_filtered::<Q, ()>()
+    }
+
+    /// Iterate over the live entities that have every component in `Q` and pass filter `F`,
+    /// yielding the fetched components
+    pub fn query_filtered<'a, Q, F>(&'a self) -> impl Iterator<Item = Q::Item> + 'a
+    where
+        Q: Fetch<'a>,
+        F: Filter,
+    {
+        let registry = &self.component_registry;
+        self.entity_list
+            .iter()
+            .filter(move |e| self.entity_manager.alive(e) && F::matches(registry, e))
+            .filter_map(move |e| Q::fetch(registry, e))
+    }
+
     /// Build an entity via `EntityBuilder`
     pub fn build_entity(&mut self) -> EntityBuilder {
         EntityBuilder::new(self)
@@ -138,7 +196,7 @@ fn add_component_default<T>(ecs: &mut World, e: Entity) -> Result<Entity>
 where
     T: Default + Sync + Send + 'static,
 {
-    let mut registry = ecs.component_registry.lock().unwrap();
+    let registry = &mut ecs.component_registry;
     registry
         .get_mut::<component_manager::Manager<T>>()
         .unwrap_or_else(|| panic!("Component type not registered for {:?}", ()))
@@ -154,7 +212,7 @@ fn add_component<T>(ecs: &mut World, e: Entity, value: T) -> Result<Entity>
 where
     T: Sync + Send + 'static,
 {
-    let mut registry = ecs.component_registry.lock().unwrap();
+    let registry = &mut ecs.component_registry;
     registry
         .get_mut::<component_manager::Manager<T>>()
         .unwrap_or_else(|| panic!("Component type not registered for {:?}", ()))
@@ -170,7 +228,7 @@ fn remove_component<U>(ecs: &mut World, e: Entity) -> Result<Entity>
 where
     U: std::fmt::Debug + Sync + Send + 'static,
 {
-    let mut registry = ecs.component_registry.lock().unwrap();
+    let registry = &mut ecs.component_registry;
     let comp_man = registry
         .get_mut::<component_manager::Manager<U>>()
         .unwrap_or_else(|| panic!("Component type not registered for {:?}", ()));
@@ -188,6 +246,77 @@ fn set_component<T>(ecs: &mut World, e: Entity, value: T)
 where
     T: Sync + Send + 'static,
 {
-    let mut registry = ecs.component_registry.lock().unwrap();
+    let registry = &mut ecs.component_registry;
     registry.get_component_mut::<T>(&e).map(|m| *m = value);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ComponentRegistry, With, Without};
+
+    #[derive(Debug, PartialEq)]
+    struct Marker;
+
+    #[test]
+    fn query() {
+        let mut world = World::new();
+        world.component_registry.register2::<u32>();
+        world.component_registry.register2::<char>();
+        world.component_registry.register2::<Marker>();
+
+        let a = world.build_entity().with(1u32).with('a').done();
+        let b = world.build_entity().with(2u32).with(Marker).done();
+        world.build_entity().with('c').done();
+
+        {
+            let expected = vec![(a, &1, Some(&'a')), (b, &2, None)];
+            let actual: Vec<_> = world.query::<(Entity, &u32, Option<&char>)>().collect();
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = vec![&1];
+            let actual: Vec<_> = world.query_filtered::<&u32, Without<Marker>>().collect();
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = vec![b];
+            let actual: Vec<_> = world.query_filtered::<Entity, With<Marker>>().collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn resources() {
+        let mut world = World::new();
+        world.insert_resource(1u32);
+        *world.resource_mut::<u32>() += 1;
+        world.insert_resource('a');
+
+        let expected = (&2, &'a', None);
+        let actual = (
+            world.resource::<u32>(),
+            world.resource::<char>(),
+            world.resources.get::<u8>(),
+        );
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn separate_registries() {
+        let mut a = World::new();
+        let mut b = World::new();
+        a.component_registry.register2::<u32>();
+        b.component_registry.register2::<u32>();
+
+        let e = a.create_entity();
+        a.add_component(e, 7u32).unwrap();
+
+        let expected = (Some(&7), None);
+        let actual = (
+            a.component_registry.get_component2::<u32>(&e),
+            b.component_registry.get_component2::<u32>(&e),
+        );
+        assert_eq!(actual, expected);
+    }
+}
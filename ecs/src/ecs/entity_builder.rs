@@ -27,7 +27,7 @@ where
     where
         U: std::fmt::Debug + Sync + Send + 'static,
     {
-        let mut registry = self.ecs.component_registry.lock().unwrap();
+        let registry = &mut self.ecs.component_registry;
         registry
             .get_mut::<component_manager::Manager<U>>()
             .unwrap_or_else(|| panic!("Component type not registered for {:?}", value))
@@ -3,6 +3,10 @@ pub enum Error {
     // MissingComponent(String),
     MissingComponent(&'static str),
     ExternalString(String),
+    /// A schedule's stages or system ordering can't be satisfied
+    InvalidSchedule(String),
+    /// A snapshot can't be written, read or restored
+    InvalidSnapshot(String),
     // External(Box<dyn std::error::Error>),
 }
 
@@ -11,6 +15,8 @@ impl std::fmt::Display for Error {
         match *self {
             Self::MissingComponent(name) => write!(f, "Component not found: {}", name),
             Self::ExternalString(ref e) => write!(f, "{}", e),
+            Self::InvalidSchedule(ref e) => write!(f, "Invalid schedule: {}", e),
+            Self::InvalidSnapshot(ref e) => write!(f, "Invalid snapshot: {}", e),
             // Self::External(ref e) => e.fmt(f),
         }
     }
@@ -0,0 +1,242 @@
+//! Typed event channels
+//!
+//! Each event type gets its own channel. Events are double buffered: `update` runs once per
+//! frame, and an event stays readable for the frame it was sent in and the frame after, so a
+//! reader that runs once a frame sees every event, whichever order systems run in.
+use std::marker::PhantomData;
+
+type Repo = anymap::Map<dyn anymap::any::Any + Sync + Send>;
+
+/// A channel of events of type `T`
+#[derive(Debug)]
+pub struct Events<T> {
+    /// Events sent last frame
+    previous: Vec<T>,
+    /// Events sent this frame
+    current: Vec<T>,
+    /// Id of the first event in `previous`. Ids count every event ever sent
+    start: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+        }
+    }
+}
+
+impl<T> Events<T> {
+    /// Send an event
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    /// Start a new frame: drop last frame's events, and keep this frame's for one more frame
+    pub fn update(&mut self) {
+        self.start += self.previous.len();
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// Drop every event. Readers see only the events sent from now on
+    pub fn clear(&mut self) {
+        self.start = self.end();
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    /// Iterate over the events sent last frame and this frame, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(self.current.iter())
+    }
+
+    /// Iterate over the events sent this frame, oldest first
+    pub fn iter_current(&self) -> impl Iterator<Item = &T> {
+        self.current.iter()
+    }
+
+    /// Create a reader that sees the events sent from now on
+    pub fn reader(&self) -> EventReader<T> {
+        EventReader {
+            next: self.end(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Get the id the next event sent will have
+    fn end(&self) -> usize {
+        self.start + self.previous.len() + self.current.len()
+    }
+}
+
+/// A subscriber's place in a channel. Each reader sees each event once
+#[derive(Debug)]
+pub struct EventReader<T> {
+    /// Id of the next unread event
+    next: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T> Clone for EventReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            next: self.next,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for EventReader<T> {
+    /// A reader that sees every event still in the channel
+    fn default() -> Self {
+        Self {
+            next: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> EventReader<T> {
+    /// Iterate over the events sent since the last read. Events dropped by `update` before they
+    /// were read are missed
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let skip = self.next.saturating_sub(events.start);
+        self.next = events.end();
+        events.iter().skip(skip)
+    }
+}
+
+/// Every event channel in a world
+pub struct EventBus {
+    channels: Repo,
+    /// Update functions, one per channel
+    updates: Vec<fn(&mut Repo)>,
+    /// Clear functions, one per channel
+    clears: Vec<fn(&mut Repo)>,
+}
+
+impl EventBus {
+    /// Create a new instance, with no channels
+    pub fn new() -> Self {
+        Self {
+            channels: Repo::new(),
+            updates: Vec::new(),
+            clears: Vec::new(),
+        }
+    }
+
+    /// Add a channel for events of type `T`, if there isn't one yet
+    pub fn register<T>(&mut self)
+    where
+        T: Sync + Send + 'static,
+    {
+        if self.channels.contains::<Events<T>>() {
+            return;
+        }
+
+        self.channels.insert(Events::<T>::default());
+        self.updates.push(|channels| {
+            if let Some(events) = channels.get_mut::<Events<T>>() {
+                events.update();
+            }
+        });
+        self.clears.push(|channels| {
+            if let Some(events) = channels.get_mut::<Events<T>>() {
+                events.clear();
+            }
+        });
+    }
+
+    /// Get the channel for events of type `T`
+    pub fn get<T>(&self) -> Option<&Events<T>>
+    where
+        T: Sync + Send + 'static,
+    {
+        self.channels.get::<Events<T>>()
+    }
+
+    /// Get the channel for events of type `T`
+    pub fn get_mut<T>(&mut self) -> Option<&mut Events<T>>
+    where
+        T: Sync + Send + 'static,
+    {
+        self.channels.get_mut::<Events<T>>()
+    }
+
+    /// Send an event
+    /// # Panic
+    /// Panics if the event type has not been registered
+    pub fn send<T>(&mut self, event: T)
+    where
+        T: std::fmt::Debug + Sync + Send + 'static,
+    {
+        self.get_mut::<T>()
+            .unwrap_or_else(|| panic!("Event type not registered for {:?}", event))
+            .send(event);
+    }
+
+    /// Start a new frame on every channel
+    pub fn update(&mut self) {
+        for update in self.updates.iter() {
+            update(&mut self.channels);
+        }
+    }
+
+    /// Drop every event on every channel
+    pub fn clear(&mut self) {
+        for clear in self.clears.iter() {
+            clear(&mut self.channels);
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn double_buffer() {
+        let mut bus = EventBus::new();
+        bus.register::<u32>();
+        let mut reader = bus.get::<u32>().unwrap().reader();
+
+        bus.send(1u32);
+        bus.update();
+        bus.send(2u32);
+        {
+            // last frame's events are still readable
+            let expected = vec![&1, &2];
+            let actual: Vec<_> = reader.read(bus.get::<u32>().unwrap()).collect();
+            assert_eq!(actual, expected);
+        }
+
+        bus.send(3u32);
+        bus.update();
+        bus.update();
+        bus.send(4u32);
+        {
+            // 3 was dropped before it was read
+            let expected = vec![&4];
+            let actual: Vec<_> = reader.read(bus.get::<u32>().unwrap()).collect();
+            assert_eq!(actual, expected);
+        }
+
+        bus.send(5u32);
+        bus.clear();
+        bus.send(6u32);
+        {
+            // 5 was cleared before it was read
+            let expected = vec![&6];
+            let actual: Vec<_> = reader.read(bus.get::<u32>().unwrap()).collect();
+            assert_eq!(actual, expected);
+        }
+    }
+}
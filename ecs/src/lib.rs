@@ -1,14 +1,23 @@
-#[macro_use]
-extern crate lazy_static;
-
 pub mod component_manager;
 pub mod component_registry;
 mod ecs;
 pub mod error;
+pub mod event;
+pub mod query;
+pub mod resource;
+pub mod schedule;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
 pub mod types;
 
 pub use component_manager::ComponentManager;
 pub use component_registry::ComponentRegistry;
 pub use entity_man::EntityManager;
+pub use event::{EventBus, EventReader, Events};
+pub use query::{Fetch, Filter, With, Without};
+pub use resource::Resources;
+pub use schedule::{Access, Schedule, System};
+#[cfg(feature = "snapshot")]
+pub use snapshot::Snapshot;
 
 pub use crate::ecs::*;
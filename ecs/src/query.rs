@@ -0,0 +1,113 @@
+//! Typed queries over the components of a `World`
+//!
+//! A query names the components to fetch as a tuple, e.g. `(Entity, &Mesh, Option<&Color>)`,
+//! and an optional filter, e.g. `Without<Preview>`. It yields the components of each live entity
+//! that has every required component and passes the filter, in the order entities were created.
+use std::marker::PhantomData;
+
+use crate::{component_registry::Registry, types::Entity, ComponentRegistry};
+
+/// Something a query can fetch for an entity
+pub trait Fetch<'a> {
+    type Item;
+
+    /// Fetch the item for `e`, or None if `e` is missing a required component
+    fn fetch(registry: &'a Registry, e: &Entity) -> Option<Self::Item>;
+}
+
+/// The entity itself
+impl<'a> Fetch<'a> for Entity {
+    type Item = Entity;
+
+    fn fetch(_registry: &'a Registry, e: &Entity) -> Option<Self::Item> {
+        Some(*e)
+    }
+}
+
+/// A required component
+impl<'a, T> Fetch<'a> for &'a T
+where
+    T: Sync + Send + 'static,
+{
+    type Item = &'a T;
+
+    fn fetch(registry: &'a Registry, e: &Entity) -> Option<Self::Item> {
+        registry.get_component2::<T>(e)
+    }
+}
+
+/// A component the entity may not have
+impl<'a, T> Fetch<'a> for Option<&'a T>
+where
+    T: Sync + Send + 'static,
+{
+    type Item = Option<&'a T>;
+
+    fn fetch(registry: &'a Registry, e: &Entity) -> Option<Self::Item> {
+        Some(registry.get_component2::<T>(e))
+    }
+}
+
+/// Decides which entities a query visits, without fetching anything
+pub trait Filter {
+    /// Check if `e` passes the filter
+    fn matches(registry: &Registry, e: &Entity) -> bool;
+}
+
+/// No filter
+impl Filter for () {
+    fn matches(_registry: &Registry, _e: &Entity) -> bool {
+        true
+    }
+}
+
+/// Only entities with component `T`
+pub struct With<T>(PhantomData<T>);
+
+impl<T> Filter for With<T>
+where
+    T: Sync + Send + 'static,
+{
+    fn matches(registry: &Registry, e: &Entity) -> bool {
+        registry.get_component2::<T>(e).is_some()
+    }
+}
+
+/// Only entities without component `T`
+pub struct Without<T>(PhantomData<T>);
+
+impl<T> Filter for Without<T>
+where
+    T: Sync + Send + 'static,
+{
+    fn matches(registry: &Registry, e: &Entity) -> bool {
+        registry.get_component2::<T>(e).is_none()
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident),+) => {
+        impl<'a, $($name: Fetch<'a>),+> Fetch<'a> for ($($name,)+) {
+            type Item = ($($name::Item,)+);
+
+            fn fetch(registry: &'a Registry, e: &Entity) -> Option<Self::Item> {
+                Some(($($name::fetch(registry, e)?,)+))
+            }
+        }
+
+        impl<$($name: Filter),+> Filter for ($($name,)+) {
+            fn matches(registry: &Registry, e: &Entity) -> bool {
+                $($name::matches(registry, e))&&+
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
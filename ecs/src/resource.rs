@@ -0,0 +1,85 @@
+//! World resources
+//!
+//! A resource is a value a world holds one of, such as a game's score or settings, instead of a
+//! component on some entity. Resources are stored and looked up by type.
+#[cfg(feature = "snapshot")]
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "snapshot")]
+use crate::snapshot::{self, Codec};
+
+type Repo = anymap::Map<dyn anymap::any::Any + Sync + Send>;
+
+/// Every resource in a world, at most one per type
+pub struct Resources {
+    repo: Repo,
+    /// Snapshot codecs of the resource types registered with `register_saved`
+    #[cfg(feature = "snapshot")]
+    pub(crate) codecs: Vec<Codec<Resources>>,
+}
+
+impl Resources {
+    /// Create a new instance, with no resources
+    pub fn new() -> Self {
+        Self {
+            repo: Repo::new(),
+            #[cfg(feature = "snapshot")]
+            codecs: Vec::new(),
+        }
+    }
+
+    /// Save the resource of type `T` in snapshots under `name`
+    #[cfg(feature = "snapshot")]
+    pub fn register_saved<T>(&mut self, name: &'static str)
+    where
+        T: Serialize + DeserializeOwned + Sync + Send + 'static,
+    {
+        snapshot::add_codec(&mut self.codecs, snapshot::resource_codec::<T>(name));
+    }
+
+    /// Add a resource, returning the resource of the same type it replaced, if any
+    pub fn insert<T>(&mut self, value: T) -> Option<T>
+    where
+        T: Sync + Send + 'static,
+    {
+        self.repo.insert(value)
+    }
+
+    /// Remove the resource of type `T`
+    pub fn remove<T>(&mut self) -> Option<T>
+    where
+        T: Sync + Send + 'static,
+    {
+        self.repo.remove::<T>()
+    }
+
+    /// Check if there's a resource of type `T`
+    pub fn contains<T>(&self) -> bool
+    where
+        T: Sync + Send + 'static,
+    {
+        self.repo.contains::<T>()
+    }
+
+    /// Get the resource of type `T`
+    pub fn get<T>(&self) -> Option<&T>
+    where
+        T: Sync + Send + 'static,
+    {
+        self.repo.get::<T>()
+    }
+
+    /// Get the resource of type `T`
+    pub fn get_mut<T>(&mut self) -> Option<&mut T>
+    where
+        T: Sync + Send + 'static,
+    {
+        self.repo.get_mut::<T>()
+    }
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
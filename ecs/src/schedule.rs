@@ -0,0 +1,395 @@
+//! Systems, and the schedule that runs them
+//!
+//! A system is one piece of logic run on some state `S`, such as a game. A schedule runs its
+//! stages in the order they were added. Within a stage, systems run in the order they were added,
+//! except where a system asks to run before or after another system in the same stage.
+use std::any::TypeId;
+
+use crate::error::Error;
+
+/// Components a system reads and writes
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Access {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+}
+
+impl Access {
+    /// Create a new instance, with no components
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a component the system reads
+    pub fn read<T: 'static>(mut self) -> Self {
+        self.reads.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Add a component the system writes
+    pub fn write<T: 'static>(mut self) -> Self {
+        self.writes.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Check if the order two systems run in can matter: one of them writes a component the
+    /// other reads or writes
+    pub fn conflicts(&self, other: &Access) -> bool {
+        let touches =
+            |access: &Access, id: &TypeId| access.reads.contains(id) || access.writes.contains(id);
+        self.writes.iter().any(|id| touches(other, id))
+            || other.writes.iter().any(|id| touches(self, id))
+    }
+}
+
+/// A unit of logic run on state `S` by a `Schedule`
+pub trait System<S, E> {
+    /// Get the name other systems use to order themselves around this one. Unique in its stage
+    fn name(&self) -> &'static str;
+
+    /// Get the components this system reads and writes
+    fn access(&self) -> Access {
+        Access::default()
+    }
+
+    /// Get the names of systems in the same stage this system has to run before
+    fn before(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Get the names of systems in the same stage this system has to run after
+    fn after(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Run the system once
+    fn run(&mut self, state: &mut S) -> Result<(), E>;
+}
+
+/// A named group of systems
+struct Stage<S, E> {
+    name: &'static str,
+    systems: Vec<Box<dyn System<S, E>>>,
+    /// True if `systems` is in run order
+    sorted: bool,
+}
+
+impl<S, E> Stage<S, E> {
+    /// Get the index of the system named `name`
+    fn position(&self, name: &str) -> crate::Result<usize> {
+        self.systems
+            .iter()
+            .position(|system| system.name() == name)
+            .ok_or_else(|| {
+                Error::InvalidSchedule(format!("no system {} in stage {}", name, self.name))
+            })
+    }
+
+    /// Get, for each system, the systems that have to run after it
+    fn edges(&self) -> crate::Result<Vec<Vec<usize>>> {
+        let mut edges = vec![Vec::new(); self.systems.len()];
+        for (i, system) in self.systems.iter().enumerate() {
+            if self.position(system.name())? != i {
+                return Err(Error::InvalidSchedule(format!(
+                    "two systems named {} in stage {}",
+                    system.name(),
+                    self.name
+                )));
+            }
+            for name in system.before() {
+                edges[i].push(self.position(name)?);
+            }
+            for name in system.after() {
+                edges[self.position(name)?].push(i);
+            }
+        }
+
+        Ok(edges)
+    }
+
+    /// Put the systems in run order. Ties keep the order the systems were added in
+    fn sort(&mut self) -> crate::Result<()> {
+        if self.sorted {
+            return Ok(());
+        }
+
+        let edges = self.edges()?;
+        let mut incoming = vec![0; edges.len()];
+        edges.iter().flatten().for_each(|j| incoming[*j] += 1);
+
+        let mut order = Vec::with_capacity(edges.len());
+        while order.len() < edges.len() {
+            let next = (0..edges.len())
+                .find(|i| incoming[*i] == 0 && !order.contains(i))
+                .ok_or_else(|| {
+                    Error::InvalidSchedule(format!("ordering cycle in stage {}", self.name))
+                })?;
+            edges[next].iter().for_each(|j| incoming[*j] -= 1);
+            order.push(next);
+        }
+
+        let mut systems: Vec<_> = self.systems.drain(..).map(Some).collect();
+        self.systems = order
+            .into_iter()
+            .filter_map(|i| systems[i].take())
+            .collect();
+        self.sorted = true;
+
+        Ok(())
+    }
+}
+
+/// Runs systems in ordered stages
+pub struct Schedule<S, E> {
+    stages: Vec<Stage<S, E>>,
+}
+
+impl<S, E> Schedule<S, E> {
+    /// Create a new instance, with no stages
+    pub fn new() -> Self {
+        Self { stages: Vec::new() }
+    }
+
+    /// Add a stage, which runs after every stage already added
+    pub fn add_stage(&mut self, name: &'static str) -> &mut Self {
+        self.stages.push(Stage {
+            name,
+            systems: Vec::new(),
+            sorted: true,
+        });
+        self
+    }
+
+    /// Add a system to the stage named `stage`
+    /// # Panic
+    /// Panics if there's no such stage
+    pub fn add_system<T>(&mut self, stage: &str, system: T) -> &mut Self
+    where
+        T: System<S, E> + 'static,
+    {
+        let stage = self
+            .stages
+            .iter_mut()
+            .find(|s| s.name == stage)
+            .unwrap_or_else(|| panic!("Stage not found: {}", stage));
+        stage.systems.push(Box::new(system));
+        stage.sorted = false;
+        self
+    }
+
+    /// Get the stage names, in run order
+    pub fn stages(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|stage| stage.name).collect()
+    }
+
+    /// Get the names of the systems in `stage`, in run order
+    pub fn systems(&mut self, stage: &str) -> crate::Result<Vec<&'static str>> {
+        let stage = self.stage_mut(stage)?;
+        stage.sort()?;
+        Ok(stage.systems.iter().map(|system| system.name()).collect())
+    }
+
+    /// Get the pairs of systems in `stage` whose order matters, judging by the components they
+    /// access, but isn't set by either of them
+    pub fn ambiguities(&mut self, stage: &str) -> crate::Result<Vec<(&'static str, &'static str)>> {
+        let stage = self.stage_mut(stage)?;
+        stage.sort()?;
+        let edges = stage.edges()?;
+
+        // systems that run after each system, directly or not
+        let mut later = vec![vec![false; edges.len()]; edges.len()];
+        for i in (0..edges.len()).rev() {
+            for j in edges[i].iter() {
+                let reach: Vec<bool> = later[i]
+                    .iter()
+                    .zip(&later[*j])
+                    .map(|(a, b)| *a || *b)
+                    .collect();
+                later[i] = reach;
+                later[i][*j] = true;
+            }
+        }
+
+        let systems = &stage.systems;
+        let mut pairs = Vec::new();
+        for i in 0..systems.len() {
+            for j in i + 1..systems.len() {
+                if !later[i][j] && systems[i].access().conflicts(&systems[j].access()) {
+                    pairs.push((systems[i].name(), systems[j].name()));
+                }
+            }
+        }
+
+        Ok(pairs)
+    }
+
+    /// Run every stage once
+    pub fn run(&mut self, state: &mut S) -> Result<(), E>
+    where
+        E: From<Error>,
+    {
+        for i in 0..self.stages.len() {
+            self.run_index(i, state)?;
+        }
+        Ok(())
+    }
+
+    /// Run the stage named `stage` once
+    pub fn run_stage(&mut self, stage: &str, state: &mut S) -> Result<(), E>
+    where
+        E: From<Error>,
+    {
+        let i = self
+            .stages
+            .iter()
+            .position(|s| s.name == stage)
+            .ok_or_else(|| Error::InvalidSchedule(format!("no stage {}", stage)))?;
+        self.run_index(i, state)
+    }
+
+    fn run_index(&mut self, i: usize, state: &mut S) -> Result<(), E>
+    where
+        E: From<Error>,
+    {
+        let stage = &mut self.stages[i];
+        stage.sort()?;
+        for system in stage.systems.iter_mut() {
+            system.run(state)?;
+        }
+        Ok(())
+    }
+
+    fn stage_mut(&mut self, stage: &str) -> crate::Result<&mut Stage<S, E>> {
+        self.stages
+            .iter_mut()
+            .find(|s| s.name == stage)
+            .ok_or_else(|| Error::InvalidSchedule(format!("no stage {}", stage)))
+    }
+}
+
+impl<S, E> Default for Schedule<S, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Appends its name to the state
+    struct Log {
+        name: &'static str,
+        after: Vec<&'static str>,
+        access: Access,
+    }
+
+    impl Log {
+        fn new(name: &'static str) -> Self {
+            Self {
+                name,
+                after: Vec::new(),
+                access: Access::new(),
+            }
+        }
+    }
+
+    impl System<Vec<&'static str>, Error> for Log {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn access(&self) -> Access {
+            self.access.clone()
+        }
+
+        fn after(&self) -> Vec<&'static str> {
+            self.after.clone()
+        }
+
+        fn run(&mut self, state: &mut Vec<&'static str>) -> crate::Result<()> {
+            state.push(self.name);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn run_order() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_stage("first")
+            .add_stage("second")
+            .add_system("second", Log::new("c"))
+            .add_system(
+                "first",
+                Log {
+                    after: vec!["b"],
+                    ..Log::new("a")
+                },
+            )
+            .add_system("first", Log::new("b"));
+
+        let expected = vec!["b", "a", "c"];
+        let mut actual = Vec::new();
+        schedule.run(&mut actual).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn invalid_order() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_stage("stage")
+            .add_system(
+                "stage",
+                Log {
+                    after: vec!["b"],
+                    ..Log::new("a")
+                },
+            )
+            .add_system(
+                "stage",
+                Log {
+                    after: vec!["a"],
+                    ..Log::new("b")
+                },
+            );
+
+        assert!(schedule.run(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn ambiguities() {
+        let mut schedule = Schedule::new();
+        let writer = || Access::new().write::<u32>();
+        schedule
+            .add_stage("stage")
+            .add_system(
+                "stage",
+                Log {
+                    access: writer(),
+                    ..Log::new("a")
+                },
+            )
+            .add_system(
+                "stage",
+                Log {
+                    access: Access::new().read::<u32>(),
+                    ..Log::new("b")
+                },
+            )
+            .add_system(
+                "stage",
+                Log {
+                    access: writer(),
+                    after: vec!["a"],
+                    ..Log::new("c")
+                },
+            );
+
+        // a and c are ordered, b reads what both write
+        let expected = vec![("a", "b"), ("b", "c")];
+        let actual = schedule.ambiguities("stage").unwrap();
+        assert_eq!(actual, expected);
+    }
+}
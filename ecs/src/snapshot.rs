@@ -0,0 +1,318 @@
+//! Saving and restoring worlds
+//!
+//! A snapshot holds a world's entities, the components of each type registered with
+//! `ComponentRegistry::register_saved`, and the resources registered with
+//! `Resources::register_saved`, each under the name it was registered with. Restoring a snapshot
+//! into a world with the same registrations gives back the same world. Event channels are not
+//! saved, and restoring clears them.
+//!
+//! Snapshots are written as JSON, which is easy to read when debugging, or as MessagePack, which
+//! is smaller and faster.
+use std::collections::BTreeMap;
+
+use entity_man::Generations;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    component_manager::{ComponentManager, Manager},
+    component_registry::{ComponentRegistry, Registry},
+    error::Error,
+    resource::Resources,
+    types::Entity,
+    Result, World,
+};
+
+/// Replaces one type's values in a store `S` with loaded ones. Can't fail
+pub(crate) type Restore<S> = Box<dyn FnOnce(&mut S)>;
+
+/// Saves and restores one type's values in a store `S`
+pub(crate) struct Codec<S> {
+    pub name: &'static str,
+    /// Get the values, or None if the store has none
+    pub save: fn(&S) -> Result<Option<Value>>,
+    /// Read saved values, or None to remove the values, without changing the store yet
+    pub load: fn(&S, Option<Value>) -> Result<Restore<S>>,
+}
+
+impl<S> Clone for Codec<S> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            save: self.save,
+            load: self.load,
+        }
+    }
+}
+
+/// Add `codec` to `codecs`, replacing the one with the same name
+pub(crate) fn add_codec<S>(codecs: &mut Vec<Codec<S>>, codec: Codec<S>) {
+    codecs.retain(|c| c.name != codec.name);
+    codecs.push(codec);
+}
+
+/// Create the codec for components of type `T`. Components are saved as a list of entity and
+/// value pairs, in the order the manager holds them
+pub(crate) fn component_codec<T>(name: &'static str) -> Codec<Registry>
+where
+    T: Serialize + DeserializeOwned + Sync + Send + 'static,
+{
+    Codec {
+        name,
+        save: |registry| {
+            let manager = registry
+                .get::<Manager<T>>()
+                .ok_or_else(|| Error::MissingComponent(std::any::type_name::<T>()))?;
+            let pairs: Vec<_> = manager.iter().collect();
+            Ok(Some(serde_json::to_value(pairs).map_err(invalid)?))
+        },
+        load: |registry, value| {
+            if registry.get::<Manager<T>>().is_none() {
+                return Err(Error::MissingComponent(std::any::type_name::<T>()));
+            }
+            let pairs: Vec<(Entity, T)> = match value {
+                Some(value) => serde_json::from_value(value).map_err(invalid)?,
+                None => Vec::new(),
+            };
+            let mut manager = Manager::new();
+            for (e, value) in pairs {
+                manager.create(e, value);
+            }
+            Ok(Box::new(move |registry: &mut Registry| {
+                if let Some(current) = registry.get_mut::<Manager<T>>() {
+                    *current = manager;
+                }
+            }))
+        },
+    }
+}
+
+/// Create the codec for the resource of type `T`
+pub(crate) fn resource_codec<T>(name: &'static str) -> Codec<Resources>
+where
+    T: Serialize + DeserializeOwned + Sync + Send + 'static,
+{
+    Codec {
+        name,
+        save: |resources| {
+            resources
+                .get::<T>()
+                .map(|value| serde_json::to_value(value).map_err(invalid))
+                .transpose()
+        },
+        load: |_, value| {
+            let value = match value {
+                Some(value) => Some(serde_json::from_value::<T>(value).map_err(invalid)?),
+                None => None,
+            };
+            Ok(Box::new(move |resources: &mut Resources| match value {
+                Some(value) => {
+                    resources.insert(value);
+                }
+                None => {
+                    resources.remove::<T>();
+                }
+            }))
+        },
+    }
+}
+
+fn invalid<E: std::fmt::Display>(err: E) -> Error {
+    Error::InvalidSnapshot(err.to_string())
+}
+
+/// A saved world
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The entity manager's state
+    pub generations: Generations,
+    /// The world's entity list
+    pub entities: Vec<Entity>,
+    /// Components, by registered name
+    pub components: BTreeMap<String, Value>,
+    /// Resources, by registered name
+    pub resources: BTreeMap<String, Value>,
+}
+
+impl Snapshot {
+    /// Write the snapshot as JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(invalid)
+    }
+
+    /// Read a snapshot written by `to_json`
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(invalid)
+    }
+
+    /// Write the snapshot as MessagePack
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        rmp_serde::to_vec(self).map_err(invalid)
+    }
+
+    /// Read a snapshot written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        rmp_serde::from_slice(bytes).map_err(invalid)
+    }
+}
+
+impl World {
+    /// Save the world's entities, and its registered components and resources
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        let mut components = BTreeMap::new();
+        for codec in self.component_registry.codecs.iter() {
+            if let Some(value) = (codec.save)(&self.component_registry)? {
+                components.insert(codec.name.to_string(), value);
+            }
+        }
+
+        let mut resources = BTreeMap::new();
+        for codec in self.resources.codecs.iter() {
+            if let Some(value) = (codec.save)(&self.resources)? {
+                resources.insert(codec.name.to_string(), value);
+            }
+        }
+
+        Ok(Snapshot {
+            generations: self.entity_manager.generations(),
+            entities: self.entity_list.clone(),
+            components,
+            resources,
+        })
+    }
+
+    /// Replace the world's entities, and its registered components and resources, with saved
+    /// ones. Registered types missing from the snapshot are cleared, and so are the event
+    /// channels. If the snapshot can't be read, the world is left as it was
+    pub fn restore(&mut self, mut snapshot: Snapshot) -> Result<()> {
+        let codecs = self.component_registry.codecs.clone();
+        let resource_codecs = self.resources.codecs.clone();
+        let unknown = snapshot
+            .components
+            .keys()
+            .find(|name| !codecs.iter().any(|c| c.name == name.as_str()))
+            .or_else(|| {
+                snapshot
+                    .resources
+                    .keys()
+                    .find(|name| !resource_codecs.iter().any(|c| c.name == name.as_str()))
+            });
+        if let Some(name) = unknown {
+            return Err(Error::InvalidSnapshot(format!(
+                "{} is not registered",
+                name
+            )));
+        }
+
+        // read everything before changing anything
+        let mut components = Vec::new();
+        for codec in codecs {
+            let value = snapshot.components.remove(codec.name);
+            components.push((codec.load)(&self.component_registry, value)?);
+        }
+        let mut resources = Vec::new();
+        for codec in resource_codecs {
+            let value = snapshot.resources.remove(codec.name);
+            resources.push((codec.load)(&self.resources, value)?);
+        }
+
+        for restore in components {
+            restore(&mut self.component_registry);
+        }
+        for restore in resources {
+            restore(&mut self.resources);
+        }
+        self.entity_manager.restore(snapshot.generations);
+        self.entity_list = snapshot.entities;
+        self.events.clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Score(u32);
+
+    fn saved_world() -> World {
+        let mut world = World::new();
+        world.component_registry.register_saved::<u32>("number");
+        world.component_registry.register_saved::<String>("name");
+        world.resources.register_saved::<Score>("score");
+        world
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut world = saved_world();
+        let a = world.build_entity().with(1u32).done();
+        let b = world.build_entity().with(2u32).with("b".to_string()).done();
+        world.destroy_entity(&a);
+        world.insert_resource(Score(10));
+        let expected = world.snapshot().unwrap();
+
+        let json = Snapshot::from_json(&expected.to_json().unwrap()).unwrap();
+        let bytes = Snapshot::from_bytes(&expected.to_bytes().unwrap()).unwrap();
+        assert_eq!(json, expected);
+        assert_eq!(bytes, expected);
+
+        let mut restored = saved_world();
+        restored.restore(bytes).unwrap();
+        let actual = restored.snapshot().unwrap();
+        assert_eq!(actual, expected);
+        assert_eq!(
+            restored.component_registry.get_component2::<String>(&b),
+            Some(&"b".to_string())
+        );
+        assert!(!restored.entity_manager.alive(&a));
+    }
+
+    #[test]
+    fn invalid() {
+        let mut world = saved_world();
+        let a = world.build_entity().with(1u32).done();
+        world.insert_resource(Score(10));
+        let mut snapshot = world.snapshot().unwrap();
+        let expected = world.snapshot().unwrap();
+
+        // the component reads fine, but the resource doesn't
+        let b = world.build_entity().with(2u32).done();
+        world.destroy_entity(&a);
+        world.insert_resource(Score(20));
+        world.events.register::<u32>();
+        world.events.send(1u32);
+        let changed = world.snapshot().unwrap();
+        snapshot
+            .resources
+            .insert("score".to_string(), Value::String("ten".to_string()));
+        assert!(world.restore(snapshot).is_err());
+        {
+            let actual = world.snapshot().unwrap();
+            assert_eq!(actual, changed);
+            assert!(world.entity_manager.alive(&b));
+        }
+
+        world.restore(expected.clone()).unwrap();
+        {
+            let actual = world.snapshot().unwrap();
+            assert_eq!(actual, expected);
+        }
+        {
+            let events = world.events.get::<u32>().unwrap();
+            assert_eq!(events.iter().count(), 0);
+        }
+    }
+
+    #[test]
+    fn unregistered() {
+        let mut world = saved_world();
+        world.insert_resource(Score(10));
+        let snapshot = world.snapshot().unwrap();
+
+        let mut other = World::new();
+        assert!(other.restore(snapshot).is_err());
+    }
+}
@@ -1,7 +1,7 @@
-use sdl2::pixels::Color;
-
+mod color;
 mod vector;
 
+pub use color::Color;
 pub use entity_man::Entity;
 pub use vector::Vector2;
 
@@ -2,6 +2,7 @@ use std::ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign};
 
 /// A 2 element vector (in the vector v scalar sense)
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "snapshot", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector2<T>
 where
     T: Clone,
@@ -1,6 +1,6 @@
 use std::collections::VecDeque;
 
-use super::{Entity, EntityManager, ENTITY_INDEX_BITS};
+use super::{Entity, EntityManager, Generations, ENTITY_INDEX_BITS};
 
 /// Keeps track of entities with generational indexes
 #[derive(Debug, PartialEq, Default)]
@@ -62,6 +62,18 @@ impl EntityManager for EntityManagerU32 {
         // add index to the recycling queue
         self.free_indices.push_back(idx);
     }
+
+    fn generations(&self) -> Generations {
+        Generations {
+            generation: self.generation.clone(),
+            free_indices: self.free_indices.iter().copied().collect(),
+        }
+    }
+
+    fn restore(&mut self, generations: Generations) {
+        self.generation = generations.generation;
+        self.free_indices = generations.free_indices.into();
+    }
 }
 
 /// ???
@@ -85,4 +97,16 @@ mod tests {
         };
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn restore() {
+        let mut manager = EntityManagerU32::new();
+        let e = manager.create();
+        manager.create();
+        manager.destroy(&e);
+
+        let mut actual = EntityManagerU32::new();
+        actual.restore(manager.generations());
+        assert_eq!(actual, manager);
+    }
 }
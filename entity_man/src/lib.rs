@@ -11,6 +11,11 @@ const ENTITY_GENERATION_MASK: u32 = (1 << ENTITY_GENERATION_BITS) - 1;
 /// id uses 30 bits split into 22 bits for the index and 8 bits for the generation. This means that
 /// we support a maximum of ~4 million simultaneous entities
 #[derive(Debug, PartialEq, Hash, Eq, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Entity {
     id: u32,
 }
@@ -31,6 +36,15 @@ impl Entity {
     }
 }
 
+/// The state of an `EntityManager`: the generation of every index, and the indices waiting to be
+/// reused, oldest first
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Generations {
+    pub generation: Vec<u8>,
+    pub free_indices: Vec<u32>,
+}
+
 /// Keeps track of entities
 pub trait EntityManager {
     /// Create a new Entity
@@ -41,6 +55,12 @@ pub trait EntityManager {
 
     /// Removes the supplied Entity and frees its index for reuse
     fn destroy(&mut self, e: &Entity);
+
+    /// Returns the state needed to recreate this manager
+    fn generations(&self) -> Generations;
+
+    /// Replaces this manager's state, as returned by `generations`
+    fn restore(&mut self, generations: Generations);
 }
 
 #[cfg(test)]
@@ -1,14 +1,18 @@
 //! Collision functions
-use crate::{component, Error, GameEvent, GameState, TetrsResult};
-use ecs::{types as cell_types, types::Entity, ComponentRegistry, World};
+use crate::{
+    component,
+    matrix::{Matrix, PieceMask},
+    Error, GameEvent, GameState, TetrsResult,
+};
+use ecs::{types as cell_types, types::Entity, ComponentRegistry};
 
+/// Check if moving the piece `e` by `delta` would leave the glass or overlap the stack
 pub fn check_collision(
     state: &mut GameState,
     e: Entity,
     delta: component::Position,
 ) -> TetrsResult<Option<GameEvent>> {
-    let ecs = &mut state.ecs;
-    let registry = ecs.component_registry.lock().unwrap();
+    let registry = &state.ecs.component_registry;
     let pos = registry
         .get_component2::<component::Position>(&e)
         .ok_or(Error::MissingComponent("Position"))?;
@@ -16,44 +20,24 @@ pub fn check_collision(
         .get_component::<component::MeshComponent, _>(&e)
         .ok_or(Error::MissingComponent("Mesh"))?;
 
-    let new_pos = *pos + delta;
-    let old_mesh: Vec<cell_types::Vector2<_>> = Vec::from(mesh.0.clone())
-        .iter()
-        .map(|c| *c + *pos)
-        .collect();
-
-    for cell in mesh.0.iter() {
-        let y = cell.y + new_pos.y;
-        let x = cell.x + new_pos.x;
-        if old_mesh.contains(&cell_types::Vector2 { x, y }) {
-            continue;
-        }
-
-        if x >= crate::GLASS_WIDTH as i32
-            || y >= crate::GLASS_HEIGHT as i32
-            || x < 0
-            || state.grid[y as usize][x as usize] != crate::BG_CELL
-        {
-            // collision
-            return Ok(Some(GameEvent::Collision(e)));
-        }
+    if collides(state.matrix(), mesh, *pos + delta) {
+        return Ok(Some(GameEvent::Collision(e)));
     }
 
     Ok(None)
 }
 
-/// Get cleared lines. Returns an empty vec (len == 0) if no lines are cleared.
+/// Check if `mesh` placed at `pos` would leave the glass or overlap an occupied cell. The
+/// current piece is never part of the matrix, so it can't collide with itself
+pub fn collides(matrix: &Matrix, mesh: &component::Mesh, pos: component::Position) -> bool {
+    matrix.collides(&PieceMask::new(mesh), pos)
+}
+
+/// Get cleared lines, bottom line first. Returns None if no lines are cleared.
 ///
-/// A cleared line is any row in the game grid that has no empty (background) cells.
+/// A cleared line is any row in the glass with every cell occupied.
 pub fn get_clear_lines(state: &mut GameState) -> Option<GameEvent> {
-    let mut cleared = Vec::new();
-    for (r, row) in state.grid.iter().enumerate().rev() {
-        // an empty column -> we're done with this row
-        if !row.iter().any(|cell| *cell == crate::BG_CELL) {
-            cleared.push(r);
-        }
-    }
-
+    let cleared = state.matrix().full_rows();
     if cleared.is_empty() {
         return None;
     }
@@ -74,11 +58,50 @@ where
     a.x.cmp(&b.x)
 }
 
-pub fn check_gameover(ecs: &mut World, e: Entity) -> TetrsResult<bool> {
-    let registry = ecs.component_registry.lock().unwrap();
+/// Get the glass cells covered by the piece `e`
+fn piece_cells(state: &GameState, e: Entity) -> TetrsResult<Vec<component::Position>> {
+    let registry = &state.ecs.component_registry;
     let pos = registry
         .get_component2::<component::Position>(&e)
         .ok_or(Error::MissingComponent("Position"))?;
+    let mesh = registry
+        .get_component::<component::MeshComponent, _>(&e)
+        .ok_or(Error::MissingComponent("Mesh"))?;
+
+    Ok(mesh.0.iter().map(|cell| *cell + *pos).collect())
+}
+
+/// Check for a block out: the piece `e`, just spawned, overlaps the stack
+pub fn check_block_out(state: &GameState, e: Entity) -> TetrsResult<bool> {
+    let cells = piece_cells(state, e)?;
+    Ok(cells.iter().any(|cell| state.matrix().blocked(*cell)))
+}
+
+/// Check for a lock out: the piece `e`, about to lock, is entirely above the visible glass
+pub fn check_lock_out(state: &GameState, e: Entity) -> TetrsResult<bool> {
+    let cells = piece_cells(state, e)?;
+    Ok(cells
+        .iter()
+        .all(|cell| cell.y < state.config().hidden_rows as i32))
+}
 
-    Ok(pos.y <= 1)
+/// Check for a top out: raising the stack by `rows` rows would push locked cells past the top of
+/// the buffer. With `rows` 1, checks if the stack has reached the top row
+pub fn check_top_out(matrix: &Matrix, rows: usize) -> bool {
+    (0..std::cmp::min(rows, matrix.height())).any(|y| matrix.row(y) != 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn top_out() {
+        let mut matrix = Matrix::new(10, 40);
+        matrix.set(component::Position { x: 0, y: 2 });
+
+        let expected = (false, true);
+        let actual = (check_top_out(&matrix, 2), check_top_out(&matrix, 3));
+        assert_eq!(actual, expected);
+    }
 }
@@ -1,25 +1,37 @@
 use std::collections::VecDeque;
 
+use serde::{Deserialize, Serialize};
+
 use ecs::{
     component_manager::Manager,
-    types::{Cell, Vector2},
+    types::{Cell, Entity, Vector2},
     ComponentRegistry,
 };
 
 pub type ColorComponent = Manager<Cell>;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Size(pub u32);
 pub type SizeComponent = Manager<Size>;
 
 pub type Position = Vector2<i32>;
 pub type PositionComponent = Manager<Position>;
 
-#[derive(Debug, Default)]
+/// Resource. Score, level and the state the scoring rules keep
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Scoring {
     pub lines: u32,
     pub level: u32,
     pub score: u32,
+    /// Number of pieces in a row that cleared lines, less one. None if the last piece didn't
+    /// clear any
+    pub combo: Option<u32>,
+    /// True if the last line clear was a tetris or a T-spin
+    pub back_to_back: bool,
+    /// Line credits toward the next level, for rules with a variable goal
+    pub progress: u32,
+    /// Internal grade, for rules that grade the player
+    pub grade: crate::score::Grade,
 }
 
 impl Scoring {
@@ -28,45 +40,85 @@ impl Scoring {
     }
 }
 
-pub type ScoringComponent = Manager<Scoring>;
+/// Resource. Upcoming pieces, next first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PieceQueue(pub VecDeque<Entity>);
+
+/// Resource. The player controlled piece, if one is in play
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CurrentPiece(pub Option<Entity>);
+
+/// Resource. The held piece, if any
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HoldPiece(pub Option<Entity>);
+
+/// Resource. State of the current piece that isn't part of its entity
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PieceState {
+    /// Kick used by the last rotation, if rotating was the last thing to move the current piece
+    pub last_rotation: Option<Position>,
+    /// Progress, in 1/65536 G, toward the current piece's next row of fall
+    pub gravity_acc: u32,
+    /// True if the current piece came from a hold. Hold is locked until the next piece locks
+    pub hold_used: bool,
+}
+
+/// Resource. Whether the game is running, and for how long
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    /// Seed the game was created with
+    pub seed: u64,
+    /// Number of frames stepped so far
+    pub frame: u64,
+    pub paused: bool,
+    pub game_over: bool,
+}
 
 /// marker component. indicates player control
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Player;
 pub type PlayerComponent = Manager<Player>;
 
 /// marker component. upcoming piece preview marker
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Preview;
 pub type PreviewComponent = Manager<Preview>;
 
 /// marker component. indicates player control
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Hold;
 pub type HoldComponent = Manager<Hold>;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Gravity {
     pub g: Vector2<i32>,
+    /// Gravity rate multiplier. Greater than 1 while soft dropping
+    pub factor: u32,
 }
 
 impl Gravity {
     pub fn new(g: Vector2<i32>) -> Self {
-        Self { g }
+        Self { g, factor: 1 }
     }
 }
 
 impl Default for Gravity {
     fn default() -> Self {
-        Self {
-            g: crate::BASE_GRAVITY,
-        }
+        Self::new(crate::BASE_GRAVITY)
     }
 }
 
 pub type GravityComponent = Manager<Gravity>;
 
-#[derive(Debug, Clone)]
+/// Type of tetromino an entity was created as
+pub type Kind = crate::Tetromino;
+pub type KindComponent = Manager<Kind>;
+
+/// Current rotation state of a tetromino
+pub type Orientation = crate::RotationState;
+pub type OrientationComponent = Manager<Orientation>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mesh(pub VecDeque<Vector2<i32>>);
 
 impl Mesh {
@@ -80,28 +132,36 @@ impl Mesh {
         }
     }
 
-    pub fn rotate_cw(&self) -> Self {
-        let mut new_mesh = Vec::from(self.0.clone());
+    /// Rotate clockwise within a `size` x `size` bounding box
+    pub fn rotate_cw(&self, size: u32) -> Self {
+        let last = size as i32 - 1;
+        let mut new_mesh = self.0.clone();
 
         for cell in new_mesh.iter_mut() {
             // apply matrix rotation
-            cell.y = -cell.x;
-            cell.x = cell.y;
+            *cell = Vector2 {
+                x: last - cell.y,
+                y: cell.x,
+            };
         }
 
-        Self::new(VecDeque::from(new_mesh))
+        Self::new(new_mesh)
     }
 
-    pub fn rotate_ccw(&mut self) -> Self {
-        let mut new_mesh = Vec::from(self.0.clone());
+    /// Rotate counter-clockwise within a `size` x `size` bounding box
+    pub fn rotate_ccw(&self, size: u32) -> Self {
+        let last = size as i32 - 1;
+        let mut new_mesh = self.0.clone();
 
         for cell in new_mesh.iter_mut() {
             // apply matrix rotation
-            cell.y = cell.x;
-            cell.x = -cell.y;
+            *cell = Vector2 {
+                x: cell.y,
+                y: last - cell.x,
+            };
         }
 
-        Self::new(VecDeque::from(new_mesh))
+        Self::new(new_mesh)
     }
 }
 
@@ -117,16 +177,17 @@ pub fn load_registry<'a, T>(component_registry: &mut T)
 where
     T: ComponentRegistry<'a>,
 {
-    component_registry.register::<ColorComponent>();
-    component_registry.register2::<Mesh>();
-    component_registry.register2::<Size>();
-    component_registry.register2::<Position>();
+    component_registry.register_saved::<Cell>("cell");
+    component_registry.register_saved::<Mesh>("mesh");
+    component_registry.register_saved::<Size>("size");
+    component_registry.register_saved::<Kind>("kind");
+    component_registry.register_saved::<Orientation>("orientation");
+    component_registry.register_saved::<Position>("position");
 
-    component_registry.register2::<Player>();
-    component_registry.register2::<Preview>();
+    component_registry.register_saved::<Player>("player");
+    component_registry.register_saved::<Preview>("preview");
 
-    component_registry.register2::<Scoring>();
-    component_registry.register2::<Gravity>();
+    component_registry.register_saved::<Gravity>("gravity");
 
-    component_registry.register2::<Hold>();
+    component_registry.register_saved::<Hold>("hold");
 }
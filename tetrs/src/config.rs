@@ -0,0 +1,168 @@
+//! Game rule configuration
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    delay::EntryDelay,
+    input::AutoShift,
+    lock::{LockDelay, LockReset},
+    matrix::MAX_WIDTH,
+    randomizer::RandomizerKind,
+    rotation::RotationKind,
+    score::ScoringKind,
+    Error, TetrsResult,
+};
+
+/// Most visible rows a glass can have
+pub const MAX_HEIGHT: usize = 100;
+
+/// Soft drop speed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SoftDropFactor {
+    /// Multiply the gravity rate while soft dropping
+    Factor(u32),
+    /// Sonic drop: move the piece to the floor at once, without locking it
+    Instant,
+}
+
+impl Default for SoftDropFactor {
+    /// Guideline soft drop: 20 times gravity
+    fn default() -> Self {
+        Self::Factor(20)
+    }
+}
+
+/// Rules for a game, chosen when the game is created
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// Rotation system used to rotate pieces
+    pub rotation: RotationKind,
+    /// Randomizer used to deal pieces
+    pub randomizer: RandomizerKind,
+    /// Lock delay rules
+    pub lock_delay: LockDelay,
+    /// Soft drop speed
+    pub soft_drop: SoftDropFactor,
+    /// Auto shift timing when holding Left or Right
+    pub auto_shift: AutoShift,
+    /// Show where the current piece will land
+    pub ghost: bool,
+    /// Fixed gravity, in 1/65536 G, instead of gravity that speeds up with the level. Capped at
+    /// 20G
+    pub gravity: Option<u32>,
+    /// Scoring and leveling rules
+    pub scoring: ScoringKind,
+    /// Allow holding the current piece
+    pub hold: bool,
+    /// Columns in the glass. Must fit an I piece, so at least 4, and at most
+    /// `matrix::MAX_WIDTH`
+    pub width: usize,
+    /// Visible rows in the glass, at least 4 and at most `MAX_HEIGHT`
+    pub height: usize,
+    /// Hidden rows above the visible glass. Pieces spawn into the bottom two, so there must be
+    /// at least 2
+    pub hidden_rows: usize,
+    /// Delays between a piece locking and the next piece spawning
+    pub entry_delay: EntryDelay,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            rotation: RotationKind::default(),
+            randomizer: RandomizerKind::default(),
+            lock_delay: LockDelay::default(),
+            soft_drop: SoftDropFactor::default(),
+            auto_shift: AutoShift::default(),
+            ghost: true,
+            gravity: None,
+            scoring: ScoringKind::default(),
+            hold: true,
+            width: 10,
+            height: 20,
+            hidden_rows: 20,
+            entry_delay: EntryDelay::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Create a new instance with the default (guideline) rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check the rules can be played: the glass must be 4 to `matrix::MAX_WIDTH` columns wide
+    /// and 4 to `MAX_HEIGHT` rows tall, with at least 2 hidden rows to spawn pieces into
+    pub fn validate(&self) -> TetrsResult<()> {
+        if self.width < 4 || self.width > MAX_WIDTH {
+            return Err(Error::InvalidConfig(format!(
+                "width {} is not between 4 and {}",
+                self.width, MAX_WIDTH
+            )));
+        }
+        if self.height < 4 || self.height > MAX_HEIGHT {
+            return Err(Error::InvalidConfig(format!(
+                "height {} is not between 4 and {}",
+                self.height, MAX_HEIGHT
+            )));
+        }
+        if self.hidden_rows < 2 {
+            return Err(Error::InvalidConfig(format!(
+                "{} hidden rows is fewer than 2",
+                self.hidden_rows
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Create a new instance with NES rules, starting on `start_level`
+    pub fn nes(start_level: u32) -> Self {
+        Self {
+            rotation: RotationKind::NRS,
+            randomizer: RandomizerKind::NesReroll,
+            lock_delay: LockDelay {
+                delay: 0,
+                ..LockDelay::default()
+            },
+            soft_drop: SoftDropFactor::default(),
+            auto_shift: AutoShift {
+                das: 16,
+                arr: 6,
+                cut: 0,
+            },
+            ghost: false,
+            gravity: None,
+            scoring: ScoringKind::Nes { start_level },
+            hold: false,
+            entry_delay: EntryDelay {
+                are: 10,
+                line_clear: 20,
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Create a new instance with TGM rules
+    pub fn tgm() -> Self {
+        Self {
+            rotation: RotationKind::ARS,
+            randomizer: RandomizerKind::TgmHistory { rolls: 4 },
+            lock_delay: LockDelay {
+                reset: LockReset::Step,
+                ..LockDelay::default()
+            },
+            soft_drop: SoftDropFactor::default(),
+            auto_shift: AutoShift::default(),
+            ghost: false,
+            gravity: None,
+            scoring: ScoringKind::Tgm,
+            hold: false,
+            entry_delay: EntryDelay {
+                are: 30,
+                line_clear: 41,
+            },
+            ..Self::default()
+        }
+    }
+}
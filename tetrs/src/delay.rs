@@ -0,0 +1,80 @@
+//! Line clear delay and entry delay (ARE)
+//!
+//! When a piece locks, the game doesn't spawn the next one right away. Cleared lines stay in
+//! the glass for the line clear delay, so they can be animated, then the next piece waits out
+//! the entry delay. Either delay may be zero, in which case it's skipped on the same frame.
+use serde::{Deserialize, Serialize};
+
+use crate::TSpin;
+/// Delays between a piece locking and the next piece spawning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryDelay {
+    /// Frames before the next piece spawns
+    pub are: u32,
+    /// Frames cleared lines stay in the glass before they're removed. Only applies when lines
+    /// are cleared
+    pub line_clear: u32,
+}
+
+impl Default for EntryDelay {
+    /// 6 frame (100ms) ARE, 20 frame (333ms) line clear delay
+    fn default() -> Self {
+        Self {
+            are: 6,
+            line_clear: 20,
+        }
+    }
+}
+
+impl EntryDelay {
+    /// No delays at all
+    pub fn none() -> Self {
+        Self {
+            are: 0,
+            line_clear: 0,
+        }
+    }
+}
+
+/// Where the game is between pieces
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Phase {
+    /// A piece is in play
+    #[default]
+    Falling,
+    /// A piece locked, clearing `lines`, which are removed when the line clear delay is over
+    LineClear {
+        lines: Vec<usize>,
+        /// Kind of T-spin the piece locked with
+        tspin: TSpin,
+        /// Gravity rate multiplier for the next piece, so a held soft drop carries over
+        factor: u32,
+        /// Frames since the piece locked
+        frame: u32,
+    },
+    /// Waiting for the next piece to spawn
+    Entry {
+        /// Gravity rate multiplier for the next piece
+        factor: u32,
+        /// Frames since the entry delay started
+        frame: u32,
+    },
+}
+
+impl Phase {
+    /// Count a frame spent in this phase
+    pub fn tick(&mut self) {
+        match self {
+            Self::Falling => (),
+            Self::LineClear { frame, .. } | Self::Entry { frame, .. } => *frame += 1,
+        }
+    }
+
+    /// Set the gravity rate multiplier the next piece spawns with
+    pub fn set_factor(&mut self, value: u32) {
+        match self {
+            Self::Falling => (),
+            Self::LineClear { factor, .. } | Self::Entry { factor, .. } => *factor = value,
+        }
+    }
+}
@@ -1,17 +1,34 @@
 //! Entity creation functions
-use crate::{component, GameState, Tetromino, TetrsResult};
-use ecs::{types::Entity, World};
+use crate::{
+    component,
+    randomizer::{GameRng, History},
+    rotation::RotationSystem,
+    Error, GameState, Tetromino, TetrsResult,
+};
+use ecs::{types::Entity, ComponentRegistry, World};
 use std::collections::VecDeque;
 
 type Position = ecs::types::Vector2<i32>;
 
-/// add a tetromino entity to the game
-fn create(ecs: &mut World, kind: Tetromino) -> TetrsResult<Entity> {
+/// Get where new pieces appear: centered, in the bottom two hidden rows above the visible
+/// glass. On narrow glasses, pieces move left so an I piece still fits
+fn spawn_position(state: &GameState) -> Position {
+    let width = state.width() as i32;
+    Position {
+        x: std::cmp::max(0, std::cmp::min((width - 1) / 2, width - 4)),
+        y: state.config().hidden_rows as i32 - 2,
+    }
+}
+
+/// add a tetromino entity to the game, in the spawn orientation of `rotation`
+fn create(ecs: &mut World, rotation: &dyn RotationSystem, kind: Tetromino) -> TetrsResult<Entity> {
     let e = ecs
         .build_entity()
-        .with(kind.new())
+        .with(rotation.spawn_mesh(kind))
         .with(kind.color())
         .with(component::Size(kind.size()))
+        .with(kind)
+        .with(rotation.spawn_state(kind))
         .done();
 
     Ok(e)
@@ -20,10 +37,16 @@ fn create(ecs: &mut World, kind: Tetromino) -> TetrsResult<Entity> {
 /// create a preview piece
 pub fn make_preview(state: &mut GameState) -> Entity {
     let ecs = &mut state.ecs;
-    let e = create(ecs, crate::Tetromino::random()).unwrap();
+    // the randomizer updates its history while drawing from the rng
+    let mut history = std::mem::take(ecs.resource_mut::<History>());
+    let kind = state
+        .randomizer
+        .next(&mut history, ecs.resource_mut::<GameRng>());
+    *ecs.resource_mut::<History>() = history;
+    let e = create(ecs, &*state.rotation, kind).unwrap();
     ecs.add_component_default::<crate::component::Preview>(e)
         .unwrap();
-    state.next_pieces.push_back(e);
+    ecs.resource_mut::<component::PieceQueue>().0.push_back(e);
 
     e
 }
@@ -36,16 +59,21 @@ fn add_player_control(ecs: &mut World, e: Entity) -> TetrsResult<Entity> {
 
 /// turn the next piece into a player controlled piece
 pub fn make_player(state: &mut GameState) -> Entity {
-    let e = state.next_pieces.pop_front().unwrap();
+    let e = state
+        .ecs
+        .resource_mut::<component::PieceQueue>()
+        .0
+        .pop_front()
+        .unwrap();
+    let spawn = spawn_position(state);
     let ecs = &mut state.ecs;
     ecs.remove_component::<component::Preview>(e).unwrap();
-    ecs.add_component::<Position>(e, Position { x: 4, y: 0 })
-        .unwrap();
+    ecs.add_component::<Position>(e, spawn).unwrap();
     ecs.add_component_default::<component::Gravity>(e).unwrap();
     add_player_control(ecs, e).unwrap();
     make_preview(state);
 
-    state.current_piece = Some(e);
+    state.set_current_piece(Some(e));
 
     e
 }
@@ -61,28 +89,45 @@ pub fn create_single_cell(ecs: &mut World, color: ecs::types::Cell, position: Po
         .done();
 }
 
-/// Create a Scoring entity
-pub fn make_scoring(state: &mut GameState) -> Entity {
-    let ecs = &mut state.ecs;
-    let e = ecs.build_entity().with(component::Scoring::new()).done();
-    state.scoring = Some(e);
-
-    e
+/// Create the Scoring resource
+pub fn make_scoring(state: &mut GameState) {
+    let scoring = component::Scoring {
+        level: state.scoring_rules.start_level(),
+        ..component::Scoring::new()
+    };
+    state.ecs.insert_resource(scoring);
 }
 
-/// Turn a Player controlled entity into a Hold entity
-pub fn hold_from_player(ecs: &mut World, e: Entity) -> Entity {
+/// Turn a Player controlled entity into a Hold entity, turned back to its spawn orientation
+pub fn hold_from_player(state: &mut GameState, e: Entity) -> TetrsResult<Entity> {
+    let ecs = &mut state.ecs;
+    {
+        let registry = &mut ecs.component_registry;
+        let kind = *registry
+            .get_component::<component::KindComponent, _>(&e)
+            .ok_or(Error::MissingComponent("Kind"))?;
+        if let Some(mesh) = registry.get_component_mut::<component::Mesh>(&e) {
+            *mesh = state.rotation.spawn_mesh(kind);
+        }
+        if let Some(orientation) = registry.get_component_mut::<component::Orientation>(&e) {
+            *orientation = state.rotation.spawn_state(kind);
+        }
+    }
+
     ecs.remove_component::<component::Player>(e).unwrap();
     ecs.remove_component::<Position>(e).unwrap();
+    ecs.remove_component::<component::Gravity>(e).unwrap();
     ecs.add_component_default::<component::Hold>(e).unwrap();
-    e
+    Ok(e)
 }
 
 /// Turn a Hold entity into a Player controlled entity
-pub fn player_from_hold(ecs: &mut World, e: Entity) -> Entity {
+pub fn player_from_hold(state: &mut GameState, e: Entity) -> Entity {
+    let spawn = spawn_position(state);
+    let ecs = &mut state.ecs;
     ecs.add_component_default::<component::Player>(e).unwrap();
-    ecs.add_component::<Position>(e, Position { x: 4, y: 0 })
-        .unwrap();
+    ecs.add_component::<Position>(e, spawn).unwrap();
+    ecs.add_component_default::<component::Gravity>(e).unwrap();
     ecs.remove_component::<component::Hold>(e).unwrap();
     e
 }
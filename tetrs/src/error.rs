@@ -5,6 +5,8 @@ pub enum Error {
     MissingComponent(&'static str),
     NoCurrentPiece,
     ExternalString(String),
+    InvalidReplay(String),
+    InvalidConfig(String),
 }
 
 impl std::fmt::Display for Error {
@@ -13,6 +15,8 @@ impl std::fmt::Display for Error {
             Self::MissingComponent(name) => write!(f, "Component not found: {}", name),
             Self::NoCurrentPiece => write!(f, "There's no current game piece!"),
             Self::ExternalString(ref e) => write!(f, "{}", e),
+            Self::InvalidReplay(ref e) => write!(f, "Invalid replay: {}", e),
+            Self::InvalidConfig(ref e) => write!(f, "Invalid config: {}", e),
         }
     }
 }
@@ -34,6 +38,8 @@ impl From<EcsError> for Error {
         match err {
             EcsError::MissingComponent(val) => Self::MissingComponent(val),
             EcsError::ExternalString(val) => Self::ExternalString(val),
+            EcsError::InvalidSchedule(val) => Self::ExternalString(val),
+            EcsError::InvalidSnapshot(val) => Self::ExternalString(val),
         }
     }
 }
@@ -1,11 +1,22 @@
+#[cfg(feature = "sdl")]
 use std::collections::HashMap;
 
+use ecs::types::Entity;
+#[cfg(feature = "sdl")]
 use sdl2::keyboard::Keycode;
 
-use crate::{component::Position, GameState, InputEvent, KeyState, RotationDirection, TetrsResult};
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "sdl")]
+use crate::KeyState;
+use crate::{
+    component::Position, GameState, InputEvent, RotationDirection, SoftDropFactor, TetrsResult,
+};
+
+#[cfg(feature = "sdl")]
 type KeyMap = HashMap<(KeyState, Keycode), InputEvent>;
 
+#[cfg(feature = "sdl")]
 pub fn default_keymap() -> KeyMap {
     let mut map = HashMap::new();
     map.insert((KeyState::Down, Keycode::Up), InputEvent::RotateCCW);
@@ -24,10 +35,16 @@ pub fn default_keymap() -> KeyMap {
 
     map.insert((KeyState::Down, Keycode::LAlt), InputEvent::Hold);
 
+    map.insert((KeyState::Up, Keycode::Left), InputEvent::LeftEnd);
+    map.insert((KeyState::Up, Keycode::A), InputEvent::LeftEnd);
+    map.insert((KeyState::Up, Keycode::Right), InputEvent::RightEnd);
+    map.insert((KeyState::Up, Keycode::D), InputEvent::RightEnd);
+
     map
 }
 
 /// Map keycodes to player movement direction
+#[cfg(feature = "sdl")]
 pub fn map_key_input(keyevent: KeyState, keycode: Keycode) -> Option<InputEvent> {
     match keyevent {
         KeyState::Down => match keycode {
@@ -42,13 +59,16 @@ pub fn map_key_input(keyevent: KeyState, keycode: Keycode) -> Option<InputEvent>
             _ => None,
         },
         KeyState::Up => match keycode {
+            Keycode::Left | Keycode::A => Some(InputEvent::LeftEnd),
             Keycode::Down | Keycode::S => Some(InputEvent::SoftDropEnd),
+            Keycode::Right | Keycode::D => Some(InputEvent::RightEnd),
             _ => None,
         },
     }
 }
 
 /// Map keycodes to player movement direction using the map `keymap`
+#[cfg(feature = "sdl")]
 pub fn map_key_input2(
     keymap: &KeyMap,
     keyevent: KeyState,
@@ -57,66 +77,362 @@ pub fn map_key_input2(
     keymap.get(&(keyevent, keycode))
 }
 
+/// Convert `ms` milliseconds to the nearest number of frames
+fn ms_to_frames(ms: u32) -> u32 {
+    (ms * crate::FRAME_RATE + 500) / 1000
+}
+
+/// Delayed Auto Shift (DAS) and Auto Repeat Rate (ARR) rules, in frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoShift {
+    /// Frames Left or Right must be held before the piece starts moving on its own
+    pub das: u32,
+    /// Frames between moves once auto shift starts. Zero moves the piece to the wall at once
+    pub arr: u32,
+    /// DAS cut: frames auto shift waits after a rotation or hold, so a charged DAS doesn't
+    /// carry a piece away right after it's turned
+    pub cut: u32,
+}
+
+impl Default for AutoShift {
+    /// 10 frame (167ms) DAS, 2 frame (33ms) ARR, no DAS cut
+    fn default() -> Self {
+        Self {
+            das: 10,
+            arr: 2,
+            cut: 0,
+        }
+    }
+}
+
+impl AutoShift {
+    /// Create a new instance from times in milliseconds, rounded to the nearest frame
+    pub fn from_ms(das: u32, arr: u32, cut: u32) -> Self {
+        Self {
+            das: ms_to_frames(das),
+            arr: ms_to_frames(arr),
+            cut: ms_to_frames(cut),
+        }
+    }
+}
+
+/// Auto shift progress. Survives between pieces, so DAS stays charged while the next piece
+/// enters
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutoShiftState {
+    pub left: bool,
+    pub right: bool,
+    /// Horizontal direction being auto shifted, -1 or 1. The last direction pressed wins
+    pub direction: i32,
+    /// Frames the direction has been held
+    pub charge: u32,
+    /// Frames since the last auto shift move
+    pub repeat: u32,
+    /// Frames left before auto shift resumes after a DAS cut
+    pub cut: u32,
+}
+
+/// Start charging DAS in `direction`, -1 for left or 1 for right
+fn start_auto_shift(state: &mut GameState, direction: i32) {
+    let shift = state.shift_mut();
+    if direction < 0 {
+        shift.left = true;
+    } else {
+        shift.right = true;
+    }
+    shift.direction = direction;
+    shift.charge = 0;
+    shift.repeat = 0;
+}
+
+/// Stop charging DAS in `direction`. Falls back on the other direction if it's still held
+fn end_auto_shift(state: &mut GameState, direction: i32) {
+    let shift = state.shift_mut();
+    if direction < 0 {
+        shift.left = false;
+    } else {
+        shift.right = false;
+    }
+    if shift.direction != direction {
+        return;
+    }
+
+    shift.direction = match (shift.left, shift.right) {
+        (true, _) => -1,
+        (_, true) => 1,
+        _ => 0,
+    };
+    shift.charge = 0;
+    shift.repeat = 0;
+}
+
+/// Delay auto shift after a rotation or hold
+fn cut_auto_shift(state: &mut GameState) {
+    let cut = state.config().auto_shift.cut;
+    state.shift_mut().cut = cut;
+}
+
+/// Charge DAS for a frame, and move the current piece once DAS is charged
+pub fn update_auto_shift(state: &mut GameState) -> TetrsResult<()> {
+    let rules = state.config().auto_shift;
+    let shift = state.shift_mut();
+    if shift.direction == 0 {
+        return Ok(());
+    }
+
+    shift.charge = shift.charge.saturating_add(1);
+    if shift.charge < rules.das {
+        return Ok(());
+    }
+    if shift.cut > 0 {
+        shift.cut -= 1;
+        return Ok(());
+    }
+
+    let step = Position {
+        x: shift.direction,
+        y: 0,
+    };
+    if rules.arr == 0 {
+        return slide_to_wall(state, step);
+    }
+
+    // the first move happens as soon as DAS is charged
+    if shift.charge > rules.das {
+        shift.repeat += 1;
+        if shift.repeat < rules.arr {
+            return Ok(());
+        }
+    }
+    shift.repeat = 0;
+    move_player(step)(state)?;
+
+    Ok(())
+}
+
+/// Move the current piece in `step`s as far as it goes. The slide counts as a single move for
+/// the lock delay
+fn slide_to_wall(state: &mut GameState, step: Position) -> TetrsResult<()> {
+    let piece = match state.current_piece() {
+        Some(piece) => piece,
+        None => return Ok(()),
+    };
+    let delta = slide_distance(state, piece, step)?;
+    if delta == Position::default() {
+        return Ok(());
+    }
+
+    crate::move_entity(&mut state.ecs, piece, delta)?;
+    crate::lock::on_piece_moved(state)?;
+    state.piece_state_mut().last_rotation = None;
+    Ok(())
+}
+
+/// Apply a player input to the game
 pub fn on_input_event(ecs: &mut crate::GameState, evt: InputEvent) {
     let on_left: MovePlayerFn = move_player(Position { x: -1, y: 0 });
     let on_right: MovePlayerFn = move_player(Position { x: 1, y: 0 });
 
+    // the game is frozen while paused, but letting go of keys still counts
+    match evt {
+        InputEvent::Pause
+        | InputEvent::SoftDropEnd
+        | InputEvent::LeftEnd
+        | InputEvent::RightEnd => (),
+        _ if ecs.status().paused => return,
+        _ => (),
+    }
+
     match evt {
         InputEvent::RotateCCW => {
-            if let Some(e) = ecs.current_piece {
-                crate::rotate_tetromino(ecs, e, RotationDirection::CCW).unwrap();
-            }
+            rotate_player(ecs, RotationDirection::CCW).unwrap();
+            cut_auto_shift(ecs);
         }
         InputEvent::RotateCW => {
-            if let Some(e) = ecs.current_piece {
-                crate::rotate_tetromino(ecs, e, RotationDirection::CW).unwrap();
-            }
+            rotate_player(ecs, RotationDirection::CW).unwrap();
+            cut_auto_shift(ecs);
         }
         InputEvent::Right => {
             on_right(ecs).unwrap();
+            start_auto_shift(ecs, 1);
+        }
+        InputEvent::RightEnd => {
+            end_auto_shift(ecs, 1);
         }
         InputEvent::Left => {
             on_left(ecs).unwrap();
+            start_auto_shift(ecs, -1);
+        }
+        InputEvent::LeftEnd => {
+            end_auto_shift(ecs, -1);
         }
         InputEvent::HardDrop => {
             hard_drop(ecs).unwrap();
         }
         InputEvent::SoftDrop => {
-            // crate::set_gravity()
+            soft_drop(ecs).unwrap();
         }
         InputEvent::SoftDropEnd => {
-            // crate::set_gravity()
+            crate::set_gravity(ecs, 1).unwrap();
         }
         InputEvent::Hold => {
             super::on_hold(ecs).unwrap();
+            cut_auto_shift(ecs);
+        }
+        InputEvent::Pause => {
+            let status = ecs.status_mut();
+            status.paused = !status.paused;
         }
-        _ => (),
     }
 }
 
-type MovePlayerFn = Box<dyn Fn(&mut GameState) -> TetrsResult<()>>;
+/// Returns true if the piece moved
+type MovePlayerFn = Box<dyn Fn(&mut GameState) -> TetrsResult<bool>>;
 fn move_player(delta: Position) -> MovePlayerFn {
-    Box::new(move |state: &mut GameState| -> TetrsResult<()> {
-        if let Some(e) = state.current_piece {
+    Box::new(move |state: &mut GameState| -> TetrsResult<bool> {
+        if let Some(e) = state.current_piece() {
             if crate::collision::check_collision(state, e, delta)?.is_none() {
                 crate::move_entity(&mut state.ecs, e, delta)?;
+                crate::lock::on_piece_moved(state)?;
+                state.piece_state_mut().last_rotation = None;
+                return Ok(true);
             }
         }
-        Ok(())
+        Ok(false)
     })
 }
 
-/// Drop the current piece until it collides
-fn hard_drop(state: &mut GameState) -> crate::TetrsResult<()> {
-    let piece = state.current_piece.ok_or(crate::Error::NoCurrentPiece)?;
-    const STEP: Position = Position { x: 0, y: 1 };
-    let mut delta = Position { x: 0, y: 1 };
+/// Rotate the current piece
+fn rotate_player(state: &mut GameState, direction: RotationDirection) -> TetrsResult<()> {
+    if let Some(e) = state.current_piece() {
+        if let Some(kick) = crate::rotate_tetromino(state, e, direction)? {
+            crate::lock::on_piece_moved(state)?;
+            state.piece_state_mut().last_rotation = Some(kick);
+        }
+    }
+    Ok(())
+}
+
+/// Start soft dropping the current piece
+fn soft_drop(state: &mut GameState) -> TetrsResult<()> {
+    match state.config().soft_drop {
+        SoftDropFactor::Factor(factor) => crate::set_gravity(state, factor),
+        SoftDropFactor::Instant => sonic_drop(state),
+    }
+}
+
+/// Get the distance `piece` can move in `step`s before it collides
+fn slide_distance(state: &mut GameState, piece: Entity, step: Position) -> TetrsResult<Position> {
+    let mut delta = step;
 
     while crate::check_collision(state, piece, delta)?.is_none() {
-        delta += STEP;
+        delta += step;
+    }
+    delta -= step;
+
+    Ok(delta)
+}
+
+/// Get the distance `piece` can fall before it collides
+pub fn drop_distance(state: &mut GameState, piece: Entity) -> TetrsResult<Position> {
+    slide_distance(state, piece, Position { x: 0, y: 1 })
+}
+
+/// Get the position where `piece` would land if hard dropped
+pub fn ghost_position(state: &mut GameState, piece: Entity) -> TetrsResult<Position> {
+    let delta = drop_distance(state, piece)?;
+    Ok(crate::get_position(state, piece)? + delta)
+}
+
+/// Drop the current piece until it collides, without locking it
+fn sonic_drop(state: &mut GameState) -> TetrsResult<()> {
+    let piece = match state.current_piece() {
+        Some(piece) => piece,
+        None => return Ok(()),
+    };
+    let delta = drop_distance(state, piece)?;
+    if delta.y == 0 {
+        return Ok(());
     }
-    delta -= STEP;
+
     crate::move_entity(&mut state.ecs, piece, delta)?;
+    let y = crate::get_position(state, piece)?.y;
+    crate::lock::on_piece_dropped(state, y)?;
+    state.piece_state_mut().last_rotation = None;
+    crate::score::add_soft_drop_points(state, delta.y as u32)
+}
 
-    Ok(())
+/// Drop the current piece until it collides, then lock it
+fn hard_drop(state: &mut GameState) -> crate::TetrsResult<()> {
+    let piece = match state.current_piece() {
+        Some(piece) => piece,
+        None => return Ok(()),
+    };
+    let delta = drop_distance(state, piece)?;
+    crate::move_entity(&mut state.ecs, piece, delta)?;
+
+    crate::lock_piece(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn auto_shift_from_ms() {
+        let expected = AutoShift {
+            das: 10,
+            arr: 0,
+            cut: 1,
+        };
+        let actual = AutoShift::from_ms(167, 0, 17);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn auto_shift_release() {
+        let mut state = GameState::with_seed(crate::Config::default(), 0).unwrap();
+        start_auto_shift(&mut state, -1);
+        start_auto_shift(&mut state, 1);
+        state.shift_mut().charge = 5;
+
+        // letting go of the last direction pressed falls back on the one still held
+        end_auto_shift(&mut state, 1);
+        {
+            let expected = (-1, 0);
+            let actual = (state.shift().direction, state.shift().charge);
+            assert_eq!(actual, expected);
+        }
+
+        end_auto_shift(&mut state, -1);
+        {
+            let expected = 0;
+            let actual = state.shift().direction;
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn instant_auto_shift() {
+        let mut state = GameState::with_seed(crate::Config::default(), 0).unwrap();
+        crate::start_game(&mut state);
+        state.config_mut().auto_shift.arr = 0;
+        let piece = state.current_piece().unwrap();
+        sonic_drop(&mut state).unwrap();
+        state.lock_mut().resets = 0;
+        start_auto_shift(&mut state, 1);
+        state.shift_mut().charge = state.config().auto_shift.das;
+
+        // the piece reaches the wall in one frame, using one lock reset
+        update_auto_shift(&mut state).unwrap();
+        {
+            let expected = 1;
+            let actual = state.lock().resets;
+            assert_eq!(actual, expected);
+        }
+        let right = Position { x: 1, y: 0 };
+        assert!(crate::check_collision(&mut state, piece, right)
+            .unwrap()
+            .is_some());
+    }
 }
@@ -4,25 +4,43 @@ use {
         // component_registry::Registry,
         types::{self as cell_types, Entity},
         ComponentRegistry,
+        Without,
     },
-    std::collections::vec_deque::VecDeque,
+    rand::SeedableRng,
 };
 
 mod collision;
 pub mod component;
+mod config;
+pub mod delay;
 mod entity;
 mod error;
 pub mod input;
-mod score;
+pub mod lock;
+pub mod matrix;
+pub mod randomizer;
+pub mod replay;
+pub mod rotation;
+pub mod schedule;
+pub mod score;
+#[cfg(feature = "sdl")]
 mod system;
 mod tetromino;
+pub mod tspin;
 mod types;
 
 pub use collision::*;
+pub use config::{Config, SoftDropFactor, MAX_HEIGHT};
+pub use delay::{EntryDelay, Phase};
 pub use entity::*;
 pub use error::Error;
+pub use lock::{update_lock_delay, LockDelay, LockReset};
+pub use randomizer::RandomizerKind;
+pub use rotation::{RotationKind, RotationState};
+#[cfg(feature = "sdl")]
 pub use system::*;
 pub use tetromino::Tetromino;
+pub use tspin::TSpin;
 pub use types::*;
 
 pub const GAME_NAME: &str = "tetrs";
@@ -40,51 +58,199 @@ pub const FONT_SIZE_LG: u16 = 24;
 pub const TEXT_COLOR: cell_types::Cell = WHITISH;
 pub const BG_CELL: cell_types::Cell = cell_types::Cell::RGB(0, 0, 0);
 pub const UI_BG: cell_types::Cell = cell_types::Cell::RGB(30, 30, 30);
-pub const GLASS_HEIGHT: usize = 20;
-pub const GLASS_WIDTH: usize = 10;
-
-// glass location
-pub const X_OFFSET: i32 = 12;
-pub const Y_OFFSET: i32 = 3;
+/// Color of the hold piece while hold is locked
+pub const HOLD_LOCKED: cell_types::Cell = cell_types::Cell::RGB(90, 90, 90);
 
 pub const BASE_GRAVITY: cell_types::Vector2<i32> = cell_types::Vector2 { x: 0, y: 1 };
 
+/// Game logic frames per second
+pub const FRAME_RATE: u32 = 60;
+/// Gravity of one row per frame. Gravity is measured in 1/65536 G
+pub const G: u32 = 65536;
+/// Fastest gravity. Pieces fall to the floor on the frame they spawn
+pub const MAX_GRAVITY: u32 = 20 * G;
+
 pub type TetrsResult<T> = Result<T, Error>;
 
+/// A game in progress. The world holds the pieces, and resources for the game's rules (`Config`),
+/// the matrix, the piece queue, the current and held pieces, the scoring, lock delay, auto shift
+/// and randomizer state, and the rng. Everything but the rules objects built from `Config` is
+/// in the world, so `snapshot` saves the whole game
 pub struct GameState {
     pub ecs: ecs::World,
-
-    pub current_piece: Option<Entity>,
-    pub scoring: Option<Entity>,
-    pub next_pieces: VecDeque<Entity>,
-    pub hold_piece: Option<Entity>,
-    pub grid: Vec<Vec<cell_types::Cell>>,
+    pub rotation: Box<dyn rotation::RotationSystem>,
+    pub randomizer: Box<dyn randomizer::Randomizer>,
+    pub scoring_rules: Box<dyn score::ScoringRules>,
+    /// Inputs to apply on the next frame
+    pub inputs: Vec<InputEvent>,
+    /// Systems run each frame
+    pub schedule: ecs::Schedule<GameState, Error>,
 }
 
 impl GameState {
     /// Create a new instance
     pub fn new() -> Self {
-        Self {
-            ecs: ecs::World::new(),
-            scoring: None,
-            current_piece: None,
-            next_pieces: VecDeque::new(),
-            hold_piece: None,
-            grid: vec![vec![BG_CELL; GLASS_WIDTH]; GLASS_HEIGHT],
-        }
+        Self::with_config(Config::default()).unwrap_or_else(|e| panic!("{}", e))
     }
 
-    // pub fn on_event(&mut self, evt: TetrsEvent) {
-    pub fn on_event(&mut self, evt: GameEvent) {
-        match evt {
-            GameEvent::ClearedLines(cleared) => {
-                on_cleared(self, cleared);
-            }
-            GameEvent::Collision(e) => {
-                on_collision(self, e).unwrap();
-            }
-            _ => (),
-        }
+    /// Create a new instance using the rules in `config`, with a random seed. Fails if the
+    /// rules can't be played
+    pub fn with_config(config: Config) -> TetrsResult<Self> {
+        Self::with_seed(config, rand::random())
+    }
+
+    /// Create a new instance using the rules in `config`. Games created with the same rules and
+    /// seed deal the same pieces. Fails if the rules can't be played
+    pub fn with_seed(config: Config, seed: u64) -> TetrsResult<Self> {
+        config.validate()?;
+        let matrix = matrix::Matrix::new(config.width, config.hidden_rows + config.height);
+        let mut ecs = ecs::World::new();
+        component::load_registry(&mut ecs.component_registry);
+        ecs.events.register::<GameEvent>();
+        let rotation = config.rotation.system();
+        let randomizer = config.randomizer.create();
+        let scoring_rules = config.scoring.create();
+        ecs.insert_resource(matrix);
+        ecs.insert_resource(randomizer::GameRng::seed_from_u64(seed));
+        ecs.insert_resource(component::PieceQueue::default());
+        ecs.insert_resource(component::CurrentPiece::default());
+        ecs.insert_resource(component::HoldPiece::default());
+        ecs.insert_resource(component::PieceState::default());
+        ecs.insert_resource(component::Status {
+            seed,
+            ..Default::default()
+        });
+        ecs.insert_resource(randomizer::History::default());
+        ecs.insert_resource(lock::LockState::default());
+        ecs.insert_resource(input::AutoShiftState::default());
+        ecs.insert_resource(Phase::default());
+        ecs.insert_resource(config);
+        register_resources(&mut ecs.resources);
+        Ok(Self {
+            ecs,
+            rotation,
+            randomizer,
+            scoring_rules,
+            inputs: Vec::new(),
+            schedule: schedule::logic_schedule(),
+        })
+    }
+
+    /// Save the game
+    pub fn snapshot(&self) -> TetrsResult<ecs::Snapshot> {
+        Ok(self.ecs.snapshot()?)
+    }
+
+    /// Replace the game with a saved one. Inputs queued for the next frame and unread events are
+    /// dropped. If the snapshot can't be read, the game is left as it was
+    pub fn restore(&mut self, snapshot: ecs::Snapshot) -> TetrsResult<()> {
+        self.ecs.restore(snapshot)?;
+        let config = self.config().clone();
+        self.rotation = config.rotation.system();
+        self.randomizer = config.randomizer.create();
+        self.scoring_rules = config.scoring.create();
+        self.inputs.clear();
+        Ok(())
+    }
+
+    /// Get the game's rules
+    pub fn config(&self) -> &Config {
+        self.ecs.resource::<Config>()
+    }
+
+    /// Get the game's rules, to change them mid-game
+    pub fn config_mut(&mut self) -> &mut Config {
+        self.ecs.resource_mut::<Config>()
+    }
+
+    /// Get the cells locked in the glass, for collision and line clears
+    pub fn matrix(&self) -> &matrix::Matrix {
+        self.ecs.resource::<matrix::Matrix>()
+    }
+
+    /// Get the player controlled piece, if one is in play
+    pub fn current_piece(&self) -> Option<Entity> {
+        self.ecs.resource::<component::CurrentPiece>().0
+    }
+
+    /// Set the player controlled piece
+    pub fn set_current_piece(&mut self, piece: Option<Entity>) {
+        self.ecs.resource_mut::<component::CurrentPiece>().0 = piece;
+    }
+
+    /// Get the held piece, if any
+    pub fn hold_piece(&self) -> Option<Entity> {
+        self.ecs.resource::<component::HoldPiece>().0
+    }
+
+    /// Get the number of columns in the glass
+    pub fn width(&self) -> usize {
+        self.config().width
+    }
+
+    /// Get the number of visible rows in the glass
+    pub fn height(&self) -> usize {
+        self.config().height
+    }
+
+    /// Get the number of rows in the matrix: the hidden rows, then the visible glass
+    pub fn grid_height(&self) -> usize {
+        self.config().hidden_rows + self.config().height
+    }
+
+    /// Get the seed this game was created with
+    pub fn seed(&self) -> u64 {
+        self.status().seed
+    }
+
+    /// Get whether the game is running, and for how long
+    pub fn status(&self) -> &component::Status {
+        self.ecs.resource::<component::Status>()
+    }
+
+    /// Get whether the game is running, to pause it or end it
+    pub fn status_mut(&mut self) -> &mut component::Status {
+        self.ecs.resource_mut::<component::Status>()
+    }
+
+    /// Get the state of the current piece
+    pub fn piece_state(&self) -> &component::PieceState {
+        self.ecs.resource::<component::PieceState>()
+    }
+
+    /// Get the state of the current piece, to change it
+    pub fn piece_state_mut(&mut self) -> &mut component::PieceState {
+        self.ecs.resource_mut::<component::PieceState>()
+    }
+
+    /// Get the current piece's lock delay progress
+    pub fn lock(&self) -> &lock::LockState {
+        self.ecs.resource::<lock::LockState>()
+    }
+
+    /// Get the current piece's lock delay progress, to change it
+    pub fn lock_mut(&mut self) -> &mut lock::LockState {
+        self.ecs.resource_mut::<lock::LockState>()
+    }
+
+    /// Get the auto shift progress
+    pub fn shift(&self) -> &input::AutoShiftState {
+        self.ecs.resource::<input::AutoShiftState>()
+    }
+
+    /// Get the auto shift progress, to change it
+    pub fn shift_mut(&mut self) -> &mut input::AutoShiftState {
+        self.ecs.resource_mut::<input::AutoShiftState>()
+    }
+
+    /// Get the line clear or entry delay in progress, if no piece is in play
+    pub fn phase(&self) -> &Phase {
+        self.ecs.resource::<Phase>()
+    }
+
+    /// Get the line clear or entry delay in progress, to change it
+    pub fn phase_mut(&mut self) -> &mut Phase {
+        self.ecs.resource_mut::<Phase>()
     }
 }
 
@@ -94,40 +260,236 @@ impl Default for GameState {
     }
 }
 
-/// Set all grid cells to initial state
+/// Register the resources a snapshot saves
+fn register_resources(resources: &mut ecs::Resources) {
+    resources.register_saved::<Config>("config");
+    resources.register_saved::<matrix::Matrix>("matrix");
+    resources.register_saved::<randomizer::GameRng>("rng");
+    resources.register_saved::<randomizer::History>("randomizer");
+    resources.register_saved::<component::PieceQueue>("piece_queue");
+    resources.register_saved::<component::CurrentPiece>("current_piece");
+    resources.register_saved::<component::HoldPiece>("hold_piece");
+    resources.register_saved::<component::PieceState>("piece_state");
+    resources.register_saved::<component::Scoring>("scoring");
+    resources.register_saved::<component::Status>("status");
+    resources.register_saved::<lock::LockState>("lock");
+    resources.register_saved::<input::AutoShiftState>("auto_shift");
+    resources.register_saved::<Phase>("phase");
+}
+
+/// Empty the matrix
 pub fn clear_grid(state: &mut GameState) {
-    state.grid = vec![vec![BG_CELL; GLASS_WIDTH]; GLASS_HEIGHT];
+    state.ecs.resource_mut::<matrix::Matrix>().clear();
+}
+
+/// Rebuild the matrix from the cells locked in the glass. The current piece is not part of the
+/// matrix
+pub fn update_grid(state: &mut GameState) {
+    clear_grid(state);
+
+    let (width, height) = (state.width(), state.grid_height());
+    let mut cells = Vec::new();
+    let locked = state
+        .ecs
+        .query_filtered::<(&component::Mesh, &component::Position), Without<component::Player>>();
+    for (mesh, pos) in locked {
+        for cell in mesh.0.iter() {
+            let component::Position { x, y } = *cell + *pos;
+            if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+                cells.push(component::Position { x, y });
+            }
+        }
+    }
+
+    let matrix = state.ecs.resource_mut::<matrix::Matrix>();
+    cells.into_iter().for_each(|cell| matrix.set(cell));
+}
+
+/// Start a game: create the scoring state, the piece previews and the first piece
+pub fn start_game(state: &mut GameState) {
+    create_entities(state);
+    update_grid(state);
+    enter_piece(state).unwrap_or_else(|e| panic!("{}", e));
+}
+
+/// End the game for `reason`
+fn end_game(state: &mut GameState, reason: GameOverReason) {
+    state.status_mut().game_over = true;
+    state.ecs.events.send(GameEvent::GameOver(reason));
+}
+
+/// Bring a newly spawned current piece into play. Ends the game if it overlaps the stack,
+/// otherwise drops it a row toward the visible glass if there's room
+fn enter_piece(state: &mut GameState) -> TetrsResult<()> {
+    let piece = state.current_piece().ok_or(Error::NoCurrentPiece)?;
+    if check_block_out(state, piece)? {
+        end_game(state, GameOverReason::BlockOut);
+        return Ok(());
+    }
+
+    if check_collision(state, piece, BASE_GRAVITY)?.is_none() {
+        move_entity(&mut state.ecs, piece, BASE_GRAVITY)?;
+    }
+
+    Ok(())
+}
+
+/// Advance the game by one frame, after applying the player's `inputs` in order.
+///
+/// This runs the game's schedule, which holds every game rule: gravity, lock delay, line clears
+/// and scoring. Nothing here needs a display, so the game can be run headless. The game runs at
+/// `FRAME_RATE` frames per second.
+pub fn step(state: &mut GameState, inputs: &[InputEvent]) -> TetrsResult<()> {
+    state.ecs.events.update();
+    if state.status().game_over {
+        return Ok(());
+    }
+
+    state.inputs.extend_from_slice(inputs);
+    // the schedule runs on the state that holds it
+    let mut schedule = std::mem::take(&mut state.schedule);
+    let result = schedule.run(state);
+    state.schedule = schedule;
+    result?;
+    state.status_mut().frame += 1;
+
+    Ok(())
+}
+
+/// Move the current piece down for one frame of gravity. Partial rows carry over to the next
+/// frame
+fn apply_gravity(state: &mut GameState) -> TetrsResult<()> {
+    let piece = state.current_piece().ok_or(Error::NoCurrentPiece)?;
+    let g = gravity(state)?;
+    let piece_state = state.piece_state_mut();
+    piece_state.gravity_acc += g;
+    let rows = piece_state.gravity_acc / G;
+    piece_state.gravity_acc %= G;
+
+    for _ in 0..rows {
+        simulate(state)?;
+        // stop at the stack, and leave the next piece for the next frame
+        if state.lock().grounded || state.current_piece() != Some(piece) {
+            break;
+        }
+    }
+
+    // a piece resting on the stack doesn't build up speed
+    if state.lock().grounded {
+        state.piece_state_mut().gravity_acc = 0;
+    }
+
+    Ok(())
 }
 
 /// Translate the active tetromino one cell downward
-pub fn simulate(state: &mut GameState) -> TetrsResult<Option<TimerEvent>> {
+pub fn simulate(state: &mut GameState) -> TetrsResult<()> {
     // TODO apply based on aspect
-    let piece = state.current_piece.ok_or(Error::NoCurrentPiece)?;
-    let delta = {
+    let piece = state.current_piece().ok_or(Error::NoCurrentPiece)?;
+    let (delta, factor) = {
         let ecs = &mut state.ecs;
-        let registry = ecs.component_registry.lock().unwrap();
+        let registry = &ecs.component_registry;
         let gravity = registry
             .get_component::<component::GravityComponent, _>(&piece)
             .ok_or(Error::MissingComponent("Gravity"))?;
-        gravity.g
+        (gravity.g, gravity.factor)
     };
 
     // check collision
     // let evt = check_collision(ecs, piece, delta)?;
-    if let Some(GameEvent::Collision(e)) = check_collision(state, piece, delta)? {
-        if let Ok(b) = check_gameover(&mut state.ecs, e) {
-            if b {
-                println!("\tGAME OVER");
-                return Ok(Some(TimerEvent::Stop));
-            }
+    if check_collision(state, piece, delta)?.is_some() {
+        // start the lock delay, unless the piece should lock on contact
+        state.lock_mut().grounded = true;
+        if state.config().lock_delay.delay == 0 {
+            return lock_piece(state);
         }
-        return on_collision(state, e);
+        return Ok(());
     }
 
     // move piece
     move_entity(&mut state.ecs, piece, delta)?;
+    let y = get_position(state, piece)?.y;
+    lock::on_piece_dropped(state, y)?;
+    state.piece_state_mut().last_rotation = None;
+
+    if factor > 1 {
+        score::add_soft_drop_points(state, 1)?;
+    }
+
+    Ok(())
+}
+
+/// Get the gravity rate multiplier of an entity
+fn get_gravity_factor(state: &GameState, e: Entity) -> TetrsResult<u32> {
+    let registry = &state.ecs.component_registry;
+    registry
+        .get_component::<component::GravityComponent, _>(&e)
+        .map(|gravity| gravity.factor)
+        .ok_or(Error::MissingComponent("Gravity"))
+}
 
-    Ok(None)
+/// Set the gravity rate multiplier of the current piece, or of the next piece if none is in
+/// play
+pub fn set_gravity(state: &mut GameState, factor: u32) -> TetrsResult<()> {
+    let piece = match state.current_piece() {
+        Some(piece) => piece,
+        None => {
+            state.phase_mut().set_factor(factor);
+            return Ok(());
+        }
+    };
+
+    let registry = &mut state.ecs.component_registry;
+    let gravity = registry
+        .get_component_mut::<component::Gravity>(&piece)
+        .ok_or(Error::MissingComponent("Gravity"))?;
+    gravity.factor = factor;
+
+    Ok(())
+}
+
+/// Get the gravity, in 1/65536 G, pulling on the current piece. Uses the configured gravity, or
+/// else the level's, times the piece's gravity rate multiplier
+pub fn gravity(state: &GameState) -> TetrsResult<u32> {
+    let factor = match state.current_piece() {
+        Some(piece) => get_gravity_factor(state, piece)?,
+        None => 1,
+    };
+    let base = match state.config().gravity {
+        Some(g) => g,
+        None => state.scoring_rules.gravity(get_level(state)?),
+    };
+
+    Ok(std::cmp::min(MAX_GRAVITY, base.saturating_mul(factor)))
+}
+
+/// Lock the current piece in place, ending the game if it locked above the visible glass
+pub fn lock_piece(state: &mut GameState) -> TetrsResult<()> {
+    let piece = state.current_piece().ok_or(Error::NoCurrentPiece)?;
+    if check_lock_out(state, piece)? {
+        end_game(state, GameOverReason::LockOut);
+        return Ok(());
+    }
+
+    on_collision(state, piece)
+}
+
+/// Get the position of an entity
+fn get_position(state: &GameState, e: Entity) -> TetrsResult<component::Position> {
+    let registry = &state.ecs.component_registry;
+    registry
+        .get_component::<component::PositionComponent, _>(&e)
+        .copied()
+        .ok_or(Error::MissingComponent("Position"))
+}
+
+/// Get the kind of tetromino an entity is
+fn get_kind(state: &GameState, e: Entity) -> TetrsResult<Tetromino> {
+    let registry = &state.ecs.component_registry;
+    registry
+        .get_component::<component::KindComponent, _>(&e)
+        .copied()
+        .ok_or(Error::MissingComponent("Kind"))
 }
 
 /// translate an entity without collision check
@@ -140,7 +502,7 @@ fn move_entity(
         return Ok(());
     }
 
-    let mut registry = ecs.component_registry.lock().unwrap();
+    let registry = &mut ecs.component_registry;
     let pos = registry
         .get_component_mut::<component::Position>(&e)
         .ok_or(Error::MissingComponent("Position"))?;
@@ -150,127 +512,72 @@ fn move_entity(
     Ok(())
 }
 
-/// Rotate a tetromino entity
+/// Rotate a tetromino entity using the game's rotation system.
+///
+/// Each of the rotation system's kicks is tried in order, and the first position where the
+/// rotated mesh fits is used. If none fit, the piece is left as is. Returns the kick used if the
+/// piece was rotated. Fails if `e` is dead.
 ///
-/// Components: mesh, size
+/// Components: mesh, kind, position, orientation
 fn rotate_tetromino(
     state: &mut GameState,
     e: Entity,
     direction: RotationDirection,
-) -> TetrsResult<()> {
-    let ecs = &mut state.ecs;
+) -> TetrsResult<Option<component::Position>> {
+    let ecs = &state.ecs;
+    // only the current piece rotates, and it's alive until it locks
     if !ecs.entity_manager.alive(&e) {
-        println!("rotate: ded ent");
-        return Ok(());
+        return Err(Error::NoCurrentPiece);
     }
 
-    let mut registry = ecs.component_registry.lock().unwrap();
-    let mesh = &registry
-        .get_component::<component::MeshComponent, _>(&e)
-        .ok_or(Error::MissingComponent("Mesh"))?;
-    let size = registry
-        .get_component::<component::SizeComponent, _>(&e)
-        .ok_or(Error::MissingComponent("Size"))?;
-    let pos = registry
+    let registry = &ecs.component_registry;
+    let kind = *registry
+        .get_component::<component::KindComponent, _>(&e)
+        .ok_or(Error::MissingComponent("Kind"))?;
+    let pos = *registry
         .get_component::<component::PositionComponent, _>(&e)
         .ok_or(Error::MissingComponent("Position"))?;
+    let from = *registry
+        .get_component::<component::OrientationComponent, _>(&e)
+        .ok_or(Error::MissingComponent("Orientation"))?;
 
-    let mut new_mesh = Vec::from(mesh.0.clone());
-    println!("pre rotate mesh: {:?}", new_mesh);
-
-    // no point rotating an O tetromino
-    if size.0 == 2 {
-        return Ok(());
-    }
-    // translate origin
-    let translate = size.0 as i32 - 2;
-    println!("size - 2: {}", translate);
-
-    let mut shift = ecs::types::Vector2 { x: 0, y: 0 };
-    for cell in new_mesh.iter_mut() {
-        // apply matrix rotation
-        let (y, x) = match direction {
-            RotationDirection::CW => {
-                let y: i32 = 1 - (cell.x as i32 - translate);
-                let x: i32 = cell.y as i32;
-                (y, x)
-            }
-            RotationDirection::CCW => {
-                let y: i32 = cell.x as i32;
-                let x: i32 = 1 - (cell.y as i32 - translate);
-                (y, x)
-            }
-        };
-
-        cell.x = x as i32;
-        cell.y = y as i32;
-        let adjusted_x = x + pos.x as i32;
-        let adjusted_y = y + pos.y as i32;
-
-        // check boundaries
-        println!("checking bounds: ({}, {})", adjusted_x, adjusted_y);
-        if adjusted_y as usize >= GLASS_HEIGHT {
-            println!(
-                "rotation failed: bounds height {} + {}\n\t {}",
-                y, pos.y, adjusted_y
-            );
-            return Ok(());
-        }
-
-        // wall kick
-        if adjusted_x >= GLASS_WIDTH as i32 {
-            let left_shift = GLASS_WIDTH as i32 - 1 - adjusted_x;
-            shift.x = std::cmp::min(shift.x, left_shift);
-            println!(
-                "x ({}) shift adjust: {}, {}",
-                adjusted_x, left_shift, shift.x
-            );
-        } else if adjusted_x < 0 {
-            let right_shift = 0 - adjusted_x;
-            shift.x = std::cmp::max(shift.x, right_shift);
-            println!(
-                "x ({}) shift adjust: {}, {}",
-                adjusted_x, right_shift, shift.x
-            );
-        }
-    }
-
-    let new_pos = cell_types::Vector2 {
-        x: (pos.x as i32 + shift.x) as i32,
-        y: (pos.y as i32 + shift.y) as i32,
+    let to = match state.rotation.rotate(kind, from, direction) {
+        Some(to) => to,
+        None => return Ok(None),
     };
+    let new_mesh = state.rotation.mesh(kind, to);
+    let matrix = state.matrix();
+    let obstructed: Vec<component::Position> = new_mesh
+        .0
+        .iter()
+        .filter(|cell| matrix.blocked(**cell + pos))
+        .copied()
+        .collect();
 
-    let mut old_mesh = mesh.0.clone();
-    for cell in old_mesh.iter_mut() {
-        *cell += *pos;
-    }
-
-    for cell in new_mesh.iter() {
-        let y = new_pos.y + cell.y;
-        let x = new_pos.x + cell.x;
-
-        // it shouldnt be able to collide with itself
-        if old_mesh.contains(&ecs::types::Vector2 { x, y }) {
-            println!("prev posi");
-            continue;
-        }
-
-        if state.grid[y as usize][x as usize] != BG_CELL {
-            println!("rotation failed: bounds occupied ({}, {})", x, y);
-            return Ok(());
-        }
-    }
+    let kick = state
+        .rotation
+        .kicks(kind, from, to, &obstructed)
+        .into_iter()
+        .find(|kick| !collides(matrix, &new_mesh, pos + *kick));
+    let kick = match kick {
+        Some(kick) => kick,
+        None => return Ok(None),
+    };
+    let new_pos = pos + kick;
 
     // update entity
-    let new_comp = component::Mesh::new(VecDeque::from(new_mesh));
+    let registry = &mut state.ecs.component_registry;
     if let Some(m) = registry.get_component_mut::<component::Mesh>(&e) {
-        *m = new_comp;
+        *m = new_mesh;
     }
     if let Some(m) = registry.get_component_mut::<component::Position>(&e) {
         *m = new_pos;
     }
+    if let Some(m) = registry.get_component_mut::<component::Orientation>(&e) {
+        *m = to;
+    }
 
-    Ok(())
+    Ok(Some(kick))
 }
 
 /// remove entities in cleared lines
@@ -283,23 +590,19 @@ fn remove_cleared(state: &mut GameState, cleared: Vec<usize>) {
     let mut move_list = Vec::new();
 
     for line in cleared {
-        for e in ecs.entity_list.iter() {
-            let registry = ecs.component_registry.lock().unwrap();
-            let pos = registry.get_component::<component::PositionComponent, _>(&e);
-            let preview = registry.get_component::<component::PreviewComponent, _>(&e);
-            if preview.is_some() || pos.is_none() {
-                continue;
-            }
-            let pos = pos.unwrap();
-
+        let cells: Vec<(Entity, component::Position)> = ecs
+            .query_filtered::<(Entity, &component::Position), Without<component::Preview>>()
+            .map(|(e, pos)| (e, *pos))
+            .collect();
+        for (e, pos) in cells {
             if pos.y == line as i32 {
                 // remove it
-                ecs.entity_manager.destroy(e);
-                move_list.retain(|x| x != e);
+                ecs.entity_manager.destroy(&e);
+                move_list.retain(|x| *x != e);
             }
             if pos.y < line as i32 {
                 // lower it
-                move_list.push(*e);
+                move_list.push(e);
             }
         }
 
@@ -312,28 +615,98 @@ fn remove_cleared(state: &mut GameState, cleared: Vec<usize>) {
     }
 }
 
-// update state afte line(s) cleared
-fn on_cleared(ecs: &mut GameState, cleared: Vec<usize>) -> Option<TimerEvent> {
+// update state afte line(s) cleared, by a piece that locked with `tspin`
+fn on_cleared(ecs: &mut GameState, cleared: Vec<usize>, tspin: TSpin) -> TetrsResult<()> {
     let line_count = cleared.len();
     remove_cleared(ecs, cleared);
-    score::update_score(ecs, line_count).unwrap_or(None)
+    update_grid(ecs);
+    score::update_score(ecs, line_count, tspin)
 }
 
 // update state following a collision event
-fn on_collision(ecs: &mut GameState, e: Entity) -> TetrsResult<Option<TimerEvent>> {
-    let mut result = None;
-    ecs.current_piece = None;
+fn on_collision(ecs: &mut GameState, e: Entity) -> TetrsResult<()> {
+    let factor = get_gravity_factor(ecs, e)?;
+    let tspin = tspin::check_tspin(ecs, e)?;
+    let kind = get_kind(ecs, e)?;
+    ecs.set_current_piece(None);
+    *ecs.lock_mut() = lock::LockState::default();
+    *ecs.piece_state_mut() = component::PieceState::default();
     decompose_mesh(ecs, e)?;
+    update_grid(ecs);
+    ecs.ecs.events.send(GameEvent::Locked(kind));
+
+    let lines = match get_clear_lines(ecs) {
+        Some(GameEvent::ClearedLines(cleared)) => cleared,
+        _ => Vec::new(),
+    };
+    if !lines.is_empty() {
+        ecs.ecs.events.send(GameEvent::ClearedLines(lines.clone()));
+    }
+    if tspin != TSpin::None {
+        ecs.ecs.events.send(GameEvent::TSpin {
+            kind: tspin,
+            lines: lines.len(),
+        });
+    }
+
+    // the cleared lines stay in the glass until the line clear delay is over
+    *ecs.phase_mut() = Phase::LineClear {
+        lines,
+        tspin,
+        factor,
+        frame: 0,
+    };
+    advance_phase(ecs)
+}
+
+/// Move on from the line clear delay or entry delay once it's over. Delays of 0 frames end on
+/// the frame they start
+fn advance_phase(state: &mut GameState) -> TetrsResult<()> {
+    let line_clear = state.config().entry_delay.line_clear;
+    if let Phase::LineClear {
+        lines,
+        tspin,
+        factor,
+        frame,
+    } = state.phase_mut()
+    {
+        if !lines.is_empty() && *frame < line_clear {
+            return Ok(());
+        }
+
+        let (lines, tspin, factor) = (std::mem::take(lines), *tspin, *factor);
+        *state.phase_mut() = Phase::Entry { factor, frame: 0 };
+        on_cleared(state, lines, tspin)?;
+        if check_top_out(state.matrix(), 1) {
+            end_game(state, GameOverReason::TopOut);
+            return Ok(());
+        }
+    }
+
+    if let Phase::Entry { factor, frame } = *state.phase() {
+        if frame < state.config().entry_delay.are {
+            return Ok(());
+        }
 
-    let cleared = get_clear_lines(ecs);
-    println!("cleared: {:?}", cleared);
-    if let Some(GameEvent::ClearedLines(cleared)) = cleared {
-        result = on_cleared(ecs, cleared);
+        *state.phase_mut() = Phase::Falling;
+        spawn_next(state, factor)?;
     }
 
-    make_player(ecs);
+    Ok(())
+}
+
+/// Bring the next piece into play, falling with gravity rate multiplier `factor`
+fn spawn_next(state: &mut GameState, factor: u32) -> TetrsResult<()> {
+    make_player(state);
+    score::on_spawn(state)?;
+    enter_piece(state)?;
 
-    Ok(result)
+    // keep soft dropping if the player is still holding the key
+    if factor != 1 {
+        set_gravity(state, factor)?;
+    }
+
+    Ok(())
 }
 
 /// Break a single mesh into multiple entities, one per cell
@@ -344,7 +717,7 @@ fn decompose_mesh(state: &mut GameState, e: Entity) -> TetrsResult<()> {
     }
 
     let (mesh, color, pos) = {
-        let registry = ecs.component_registry.lock().unwrap();
+        let registry = &ecs.component_registry;
         let mesh = registry
             .get_component::<component::MeshComponent, _>(&e)
             .ok_or(Error::MissingComponent("Mesh"))?;
@@ -373,41 +746,308 @@ fn get_scoring<F>(state: &GameState, f: F) -> TetrsResult<u32>
 where
     F: Fn(&component::Scoring) -> u32,
 {
-    let e = state.scoring.unwrap();
-    let ecs = &state.ecs;
-    let registry = ecs.component_registry.lock().unwrap();
-    registry
-        .get_component::<component::ScoringComponent, _>(&e)
+    state
+        .ecs
+        .resources
+        .get::<component::Scoring>()
         .ok_or(Error::MissingComponent("Scoring"))
         .map(f)
 }
 
-fn get_lines(state: &GameState) -> TetrsResult<u32> {
+/// Get the number of lines cleared
+pub fn get_lines(state: &GameState) -> TetrsResult<u32> {
     get_scoring(state, |s| s.lines)
 }
 
-fn get_level(state: &GameState) -> TetrsResult<u32> {
+/// Get the current level, starting from 0
+pub fn get_level(state: &GameState) -> TetrsResult<u32> {
     get_scoring(state, |s| s.level)
 }
 
-fn get_score(state: &GameState) -> TetrsResult<u32> {
+/// Get the current score
+pub fn get_score(state: &GameState) -> TetrsResult<u32> {
     get_scoring(state, |s| s.score)
 }
 
-/// adjust state after a Hold event
+/// Get the player's grade, if the scoring rules grade players
+pub fn get_grade(state: &GameState) -> TetrsResult<Option<&'static str>> {
+    state
+        .ecs
+        .resources
+        .get::<component::Scoring>()
+        .ok_or(Error::MissingComponent("Scoring"))
+        .map(|scoring| state.scoring_rules.grade(scoring))
+}
+
+/// adjust state after a Hold event. Hold is allowed once per piece, until the next piece locks
 fn on_hold(state: &mut GameState) -> TetrsResult<()> {
-    let player = state.current_piece.unwrap();
+    let player = match state.current_piece() {
+        Some(player) if state.config().hold && !state.piece_state().hold_used => player,
+        _ => return Ok(()),
+    };
+    let factor = get_gravity_factor(state, player)?;
+    let kind = get_kind(state, player)?;
+    state.piece_state_mut().hold_used = true;
 
     // use the existing hold piece, or take a new player from the queue
-    if let Some(hold) = state.hold_piece {
-        player_from_hold(&mut state.ecs, hold);
-        state.current_piece = Some(hold);
+    if let Some(hold) = state.hold_piece() {
+        player_from_hold(state, hold);
+        state.set_current_piece(Some(hold));
     } else {
         make_player(state);
     }
 
-    hold_from_player(&mut state.ecs, player);
-    state.hold_piece = Some(player);
+    hold_from_player(state, player)?;
+    state.ecs.resource_mut::<component::HoldPiece>().0 = Some(player);
+    state.ecs.events.send(GameEvent::Hold(kind));
+    *state.lock_mut() = lock::LockState::default();
+    let piece_state = state.piece_state_mut();
+    piece_state.last_rotation = None;
+    piece_state.gravity_acc = 0;
+
+    enter_piece(state)?;
+    // keep soft dropping if the player is still holding the key
+    if factor != 1 {
+        set_gravity(state, factor)?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn step_headless() {
+        let mut state = GameState::with_seed(Config::default(), 0).unwrap();
+        start_game(&mut state);
+
+        // the first piece spawns in the buffer, then drops a row toward the visible glass
+        {
+            let expected = state.config().hidden_rows as i32 - 1;
+            let actual = get_position(&state, state.current_piece().unwrap())
+                .unwrap()
+                .y;
+            assert_eq!(actual, expected);
+        }
+
+        // the first piece lands on the floor and becomes part of the matrix
+        let kind = get_kind(&state, state.current_piece().unwrap()).unwrap();
+        let mut reader = state.ecs.events.get::<GameEvent>().unwrap().reader();
+        step(&mut state, &[InputEvent::HardDrop]).unwrap();
+        {
+            let expected = vec![GameEvent::Locked(kind)];
+            let events = state.ecs.events.get::<GameEvent>().unwrap();
+            let actual: Vec<_> = reader.read(events).cloned().collect();
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = 4;
+            let matrix = state.matrix();
+            let actual: u32 = (0..matrix.height())
+                .map(|y| matrix.row(y).count_ones())
+                .sum();
+            assert_eq!(actual, expected);
+        }
+
+        // no lines were cleared, so the next piece spawns once the entry delay is over
+        for _ in 1..state.config().entry_delay.are {
+            step(&mut state, &[]).unwrap();
+        }
+        assert_eq!(state.current_piece(), None);
+        step(&mut state, &[]).unwrap();
+        assert!(state.current_piece().is_some());
+
+        // the next piece falls on its own, a row per frame at 1G
+        state.config_mut().gravity = Some(G);
+        let piece = state.current_piece().unwrap();
+        let y = get_position(&state, piece).unwrap().y;
+        step(&mut state, &[]).unwrap();
+        {
+            let expected = y + 1;
+            let actual = get_position(&state, piece).unwrap().y;
+            assert_eq!(actual, expected);
+        }
+
+        // holding Right with ARR 0 slides the piece to the wall once DAS is charged
+        state.config_mut().gravity = Some(0);
+        state.config_mut().auto_shift.arr = 0;
+        step(&mut state, &[InputEvent::Right]).unwrap();
+        for _ in 1..state.config().auto_shift.das {
+            step(&mut state, &[]).unwrap();
+        }
+        let right = component::Position { x: 1, y: 0 };
+        assert!(check_collision(&mut state, piece, right).unwrap().is_some());
+
+        // a held piece goes back to its spawn orientation, and hold locks until the next piece
+        // locks
+        step(&mut state, &[InputEvent::RotateCW, InputEvent::Hold]).unwrap();
+        {
+            let expected = RotationState::Zero;
+            let registry = &state.ecs.component_registry;
+            let actual = *registry
+                .get_component::<component::OrientationComponent, _>(&piece)
+                .unwrap();
+            assert_eq!(actual, expected);
+        }
+        let current = state.current_piece();
+        step(&mut state, &[InputEvent::Hold]).unwrap();
+        {
+            let expected = (Some(piece), current);
+            let actual = (state.hold_piece(), state.current_piece());
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn top_out() {
+        let mut state = GameState::with_seed(Config::default(), 0).unwrap();
+        start_game(&mut state);
+        let mut reader = state.ecs.events.get::<GameEvent>().unwrap().reader();
+
+        // a piece with a cell in the top row of the buffer, and one in the visible glass so it
+        // doesn't lock out
+        let piece = state.current_piece().unwrap();
+        let y = get_position(&state, piece).unwrap().y;
+        let hidden_rows = state.config().hidden_rows as i32;
+        let mesh = vec![
+            component::Position { x: 0, y: -y },
+            component::Position {
+                x: 0,
+                y: hidden_rows - y,
+            },
+        ];
+        state
+            .ecs
+            .set_component(piece, component::Mesh::new(mesh.into()));
+        lock_piece(&mut state).unwrap();
+
+        let expected = (true, Some(GameEvent::GameOver(GameOverReason::TopOut)));
+        let events = state.ecs.events.get::<GameEvent>().unwrap();
+        let actual = (
+            state.status().game_over,
+            reader.read(events).last().cloned(),
+        );
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn glass_size() {
+        for (width, height) in [(3, 20), (70, 20), (10, 3), (10, 101)] {
+            let config = Config {
+                width,
+                height,
+                ..Config::default()
+            };
+            assert!(GameState::with_seed(config, 0).is_err());
+        }
+        let config = Config {
+            hidden_rows: 1,
+            ..Config::default()
+        };
+        assert!(GameState::with_seed(config, 0).is_err());
+        let config = Config {
+            width: 4,
+            height: 100,
+            ..Config::default()
+        };
+        assert!(GameState::with_seed(config, 0).is_ok());
+    }
+
+    /// Inputs for frame `frame` of a game that plays a piece every 45 frames: it holds some
+    /// pieces, taps the rest to a different column each time, rotates them, soft drops, charges
+    /// DAS and hard drops
+    fn scripted_inputs(frame: u64) -> Vec<InputEvent> {
+        let (piece, offset) = ((frame / 45) as i64, (frame % 45) as i64);
+        let shift = piece * 3 % 9 - 4;
+        let (press, release) = if shift < 0 {
+            (InputEvent::Left, InputEvent::LeftEnd)
+        } else {
+            (InputEvent::Right, InputEvent::RightEnd)
+        };
+        // the next piece spawns once the entry delay after the hard drop is over
+        match offset {
+            10 if piece % 4 == 1 => vec![InputEvent::Hold],
+            11..=18 if offset - 10 <= 2 * shift.abs() => match offset % 2 {
+                1 => vec![press],
+                _ => vec![release],
+            },
+            20 if piece % 2 == 0 => vec![InputEvent::RotateCW],
+            24 => vec![InputEvent::SoftDrop],
+            28 => vec![InputEvent::SoftDropEnd],
+            34 => vec![press],
+            40 => vec![release],
+            44 => vec![InputEvent::HardDrop],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get what the player sees: the matrix, the score, where the current piece is, and the
+    /// upcoming pieces
+    fn observe(
+        state: &GameState,
+    ) -> (
+        matrix::Matrix,
+        (u32, u32, u32),
+        Option<component::Position>,
+        Vec<Tetromino>,
+    ) {
+        let scoring = (
+            get_score(state).unwrap(),
+            get_lines(state).unwrap(),
+            get_level(state).unwrap(),
+        );
+        let position = state
+            .current_piece()
+            .map(|piece| get_position(state, piece).unwrap());
+        let queue = state
+            .ecs
+            .resource::<component::PieceQueue>()
+            .0
+            .iter()
+            .map(|e| get_kind(state, *e).unwrap())
+            .collect();
+        (state.matrix().clone(), scoring, position, queue)
+    }
+
+    #[test]
+    fn snapshot() {
+        let config = Config {
+            randomizer: RandomizerKind::TgmHistory { rolls: 4 },
+            ..Config::default()
+        };
+        let mut state = GameState::with_seed(config, 7).unwrap();
+        start_game(&mut state);
+        // stop mid-piece, with DAS charging and a piece held
+        for frame in 0..396 {
+            step(&mut state, &scripted_inputs(frame)).unwrap();
+        }
+        let snapshot = state.snapshot().unwrap();
+
+        // restore into games created with other rules and seeds
+        let json = ecs::Snapshot::from_json(&snapshot.to_json().unwrap()).unwrap();
+        let bytes = ecs::Snapshot::from_bytes(&snapshot.to_bytes().unwrap()).unwrap();
+        let mut restored = Vec::new();
+        for snapshot in [json, bytes] {
+            let mut game = GameState::with_seed(Config::default(), 1).unwrap();
+            start_game(&mut game);
+            game.restore(snapshot).unwrap();
+            restored.push(game);
+        }
+
+        // the restored games play on exactly like the original
+        for frame in 396..1000 {
+            let inputs = scripted_inputs(frame);
+            step(&mut state, &inputs).unwrap();
+            let expected = observe(&state);
+            for game in restored.iter_mut() {
+                step(game, &inputs).unwrap();
+                let actual = observe(game);
+                assert_eq!(actual, expected, "frame {}", frame);
+            }
+        }
+        // the game was still going, so every frame compared pieces in play
+        assert!(!state.status().game_over);
+    }
+}
@@ -0,0 +1,120 @@
+//! Lock delay
+//!
+//! A piece touching the stack doesn't lock right away. Instead a timer starts, and the piece
+//! locks once the timer runs out while it's still on the ground. Depending on the reset rule,
+//! moving or rotating the piece restarts the timer.
+use serde::{Deserialize, Serialize};
+
+use crate::{component::Position, GameState, TetrsResult};
+const DOWN: Position = Position { x: 0, y: 1 };
+
+/// Rules for restarting the lock delay timer
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockReset {
+    /// Move reset: each successful move or rotation restarts the timer, up to a limited number
+    /// of times. Reaching a new lowest row restores the allowance.
+    #[default]
+    Move,
+    /// Step reset: only moving down a row restarts the timer
+    Step,
+    /// Infinity: each successful move or rotation restarts the timer, without limit
+    Infinity,
+}
+
+/// Lock delay rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockDelay {
+    /// Frames a piece may rest on the ground before it locks. Zero locks on contact
+    pub delay: u32,
+    /// How the timer is restarted
+    pub reset: LockReset,
+    /// Number of restarts allowed by `LockReset::Move`
+    pub max_resets: u32,
+}
+
+impl Default for LockDelay {
+    /// Guideline lock delay: 30 frames (500ms) with up to 15 move resets
+    fn default() -> Self {
+        Self {
+            delay: 30,
+            reset: LockReset::Move,
+            max_resets: 15,
+        }
+    }
+}
+
+/// Lock delay progress of the current piece
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockState {
+    /// Frames the piece has spent on the ground since the last restart
+    pub elapsed: u32,
+    /// Number of move resets used
+    pub resets: u32,
+    /// True if the piece is resting on the stack or the floor
+    pub grounded: bool,
+    /// Lowest row the piece has reached
+    pub lowest: i32,
+}
+
+/// Check if the current piece would collide by moving down a row
+fn is_grounded(state: &mut GameState) -> TetrsResult<bool> {
+    match state.current_piece() {
+        Some(e) => Ok(crate::check_collision(state, e, DOWN)?.is_some()),
+        None => Ok(false),
+    }
+}
+
+/// Update lock delay after the current piece was successfully moved sideways or rotated
+pub fn on_piece_moved(state: &mut GameState) -> TetrsResult<()> {
+    let grounded = is_grounded(state)?;
+    let rules = state.config().lock_delay;
+    let lock = state.lock_mut();
+
+    match rules.reset {
+        LockReset::Move => {
+            if (lock.grounded || grounded) && lock.resets < rules.max_resets {
+                lock.elapsed = 0;
+                lock.resets += 1;
+            }
+        }
+        LockReset::Infinity => lock.elapsed = 0,
+        LockReset::Step => (),
+    }
+    lock.grounded = grounded;
+
+    Ok(())
+}
+
+/// Update lock delay after the current piece moved down to row `y`
+pub fn on_piece_dropped(state: &mut GameState, y: i32) -> TetrsResult<()> {
+    let grounded = is_grounded(state)?;
+    let lock = state.lock_mut();
+
+    if y > lock.lowest {
+        lock.lowest = y;
+        lock.elapsed = 0;
+        lock.resets = 0;
+    }
+    lock.grounded = grounded;
+
+    Ok(())
+}
+
+/// Advance the lock delay timer by a frame, locking the current piece if it has spent too long
+/// on the ground
+pub fn update_lock_delay(state: &mut GameState) -> TetrsResult<()> {
+    if state.current_piece().is_none() || !state.lock().grounded {
+        return Ok(());
+    }
+
+    let rules = state.config().lock_delay;
+    let lock = state.lock_mut();
+    lock.elapsed += 1;
+
+    let out_of_resets = rules.reset == LockReset::Move && lock.resets >= rules.max_resets;
+    if lock.elapsed >= rules.delay || out_of_resets {
+        return crate::lock_piece(state);
+    }
+
+    Ok(())
+}
@@ -1,14 +1,79 @@
-use std::{sync::mpsc, thread, time};
+use std::{env, thread, time};
 
 use cell_engine::gfx;
 use sdl2::{event::Event, keyboard::Keycode, render::Canvas, ttf, video::Window};
-use tetrs::GameState;
+use tetrs::{
+    replay::{Replay, ReplayPlayer},
+    score::{LevelGoal, ScoringKind},
+    Config, GameEvent, GameState,
+};
+
+/// How the game's inputs are recorded or played back
+enum Mode {
+    Play,
+    /// Record the game to a replay file
+    Record(String),
+    /// Play back a replay file instead of reading the keyboard
+    Replay(String),
+}
+
+const USAGE: &str = "usage: tetrs [--record <file> | --replay <file>] \
+                     [--mode guideline|variable|nes|tgm] [--level <nes start level>] \
+                     [--width <columns>] [--height <rows>]";
+
+/// Read the command line arguments: how inputs are recorded or played back, `--record <file>`
+/// or `--replay <file>`, the game mode, `--mode <name>`, which picks the rules, and the glass
+/// size
+fn parse_args() -> (Mode, Config) {
+    let usage = || -> ! {
+        eprintln!("{}", USAGE);
+        std::process::exit(1);
+    };
+
+    let mut mode = Mode::Play;
+    let mut game_mode = String::from("guideline");
+    let mut level = 0;
+    let mut width = None;
+    let mut height = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--record" => mode = Mode::Record(value),
+            "--replay" => mode = Mode::Replay(value),
+            "--mode" => game_mode = value,
+            "--level" => level = value.parse().unwrap_or_else(|_| usage()),
+            "--width" => width = Some(value.parse().unwrap_or_else(|_| usage())),
+            "--height" => height = Some(value.parse().unwrap_or_else(|_| usage())),
+            _ => usage(),
+        }
+    }
+
+    let mut config = match game_mode.as_str() {
+        "guideline" => Config::default(),
+        "variable" => Config {
+            scoring: ScoringKind::Guideline {
+                goal: LevelGoal::Variable,
+            },
+            ..Config::default()
+        },
+        "nes" => Config::nes(level),
+        "tgm" => Config::tgm(),
+        _ => usage(),
+    };
+    config.width = width.unwrap_or(config.width);
+    config.height = height.unwrap_or(config.height);
+    if let Err(e) = config.validate() {
+        eprintln!("{}", e);
+        usage();
+    }
+
+    (mode, config)
+}
 
 fn main() {
     const CANVAS_WIDTH: u32 = 720_u32;
     const CANVAS_HEIGHT: u32 = 540;
-    const ROWS: u32 = 36;
-    const CELL_WIDTH: u32 = CANVAS_WIDTH / ROWS;
 
     let (mut canvas, mut event_pump) = gfx::init(tetrs::GAME_NAME, CANVAS_WIDTH, CANVAS_HEIGHT);
 
@@ -18,7 +83,8 @@ fn main() {
     println!("font path: {}", font_path);
     let game_font = gfx::init_font(&ttf_context, &font_path, tetrs::FONT_SIZE_MD);
 
-    run_game(&mut canvas, &mut event_pump, &game_font, CELL_WIDTH);
+    let (mode, config) = parse_args();
+    run_game(&mut canvas, &mut event_pump, &game_font, mode, config);
 }
 
 /// Panic while printing the error
@@ -26,139 +92,122 @@ fn panic_with_err(e: impl std::error::Error) {
     panic!("{}", e)
 }
 
-type MapInputFn = Box<dyn FnMut(&mut GameState, Keycode)>;
-fn map_input(keystate: tetrs::KeyState) -> MapInputFn {
-    Box::new(move |mut state, k| {
-        let evt = tetrs::input::map_key_input(keystate, k);
-        if let Some(evt) = evt {
-            tetrs::input::on_input_event(&mut state, evt);
-        }
-    })
-}
-
 fn run_game(
     canvas: &mut Canvas<Window>,
     event_pump: &mut sdl2::EventPump,
     font: &ttf::Font,
-    cell_width: u32,
+    mode: Mode,
+    config: Config,
 ) {
-    let mut state = GameState::new();
-    tetrs::component::load_registry(&mut *state.ecs.component_registry.lock().unwrap());
-    tetrs::create_entities(&mut state);
-    let _keymap = tetrs::input::default_keymap();
-
-    let (to_game, game_rx) = mpsc::channel::<tetrs::TimerEvent>();
-    let (to_timer, timer_rx) = mpsc::channel();
-    create_timer(to_game, timer_rx, 300);
+    let mut player = None;
+    let mut state = match mode {
+        Mode::Replay(ref path) => {
+            let replay = Replay::load(path).unwrap_or_else(|e| panic!("{}", e));
+            let state = replay.new_game().unwrap_or_else(|e| panic!("{}", e));
+            player = Some(ReplayPlayer::new(replay));
+            state
+        }
+        _ => GameState::with_config(config).unwrap_or_else(|e| panic!("{}", e)),
+    };
+    let mut recording = match mode {
+        Mode::Record(_) => Some(Replay::for_game(&state)),
+        _ => None,
+    };
+    println!("seed: {}", state.seed());
+    tetrs::start_game(&mut state);
+    let cell_width = tetrs::Layout::new(&state).cell_width(canvas.output_size().unwrap());
+    let mut screen = tetrs::Screen {
+        state,
+        canvas,
+        font,
+        cell_width,
+    };
+    let mut render = tetrs::render_schedule();
+
+    let frame_time = time::Duration::from_secs(1) / tetrs::FRAME_RATE;
+    let mut last_time = time::Instant::now();
+    // time not yet simulated
+    let mut lag = time::Duration::from_secs(0);
+    let mut inputs = Vec::new();
+    let mut events = screen.state.ecs.events.get::<GameEvent>().unwrap().reader();
 
     println!("starting game loop");
     'game: loop {
         for event in event_pump.poll_iter() {
-            match event {
+            let evt = match event {
                 // exit on escape key
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => {
-                    to_timer.send(tetrs::TimerEvent::Stop).unwrap();
                     break 'game;
                 }
 
+                // user input keys, ignored during playback
+                _ if player.is_some() => continue,
+                // auto shift is handled by the game, so ignore OS key repeats
                 Event::KeyDown {
-                    keycode: Some(Keycode::P),
+                    keycode: Some(k),
+                    repeat: false,
                     ..
-                } => {
-                    to_timer.send(tetrs::TimerEvent::Pause).unwrap();
-                    continue 'game;
-                }
-
-                // user input keys
-                Event::KeyDown {
-                    keycode: Some(k), ..
-                } => {
-                    map_input(tetrs::KeyState::Down)(&mut state, k);
-                }
+                } => tetrs::input::map_key_input(tetrs::KeyState::Down, k),
                 Event::KeyUp {
                     keycode: Some(k), ..
-                } => {
-                    map_input(tetrs::KeyState::Up)(&mut state, k);
-                }
+                } => tetrs::input::map_key_input(tetrs::KeyState::Up, k),
 
-                _ => continue 'game,
-            }
+                _ => continue,
+            };
+            inputs.extend(evt);
         }
 
-        tetrs::clear_grid(&mut state);
-
-        tetrs::draw_ui_bg(canvas, cell_width).unwrap_or_else(panic_with_err);
-        tetrs::draw_ui(&mut state, canvas, cell_width, font).unwrap_or_else(panic_with_err);
-        tetrs::draw_entities(&mut state, canvas, cell_width).unwrap_or_else(panic_with_err);
-        tetrs::draw_previews(&mut state, canvas, cell_width).unwrap_or_else(panic_with_err);
-        tetrs::draw_hold(&mut state, canvas, cell_width).unwrap_or_else(panic_with_err);
-        gfx::display_frame(canvas);
-
-        // stuff that happens each game tick
-        match game_rx.try_recv() {
-            Err(mpsc::TryRecvError::Empty) => (),
-            Err(mpsc::TryRecvError::Disconnected) => {
-                println!("Timer disconnected");
-                return;
+        // run the game logic at a fixed rate, however often frames are drawn
+        let now = time::Instant::now();
+        lag += now.duration_since(last_time);
+        last_time = now;
+        while lag >= frame_time {
+            // once the game is over, only draw the final board until the player quits
+            if screen.state.status().game_over {
+                lag = time::Duration::from_secs(0);
+                break;
             }
-            _ => {
-                // update entities
-                match tetrs::simulate(&mut state) {
-                    Err(e) => {
-                        println!("err: {}", e);
+            lag -= frame_time;
+
+            let state = &mut screen.state;
+            let result = match player.as_mut() {
+                Some(player) => player.step(state),
+                None => {
+                    if let Some(replay) = recording.as_mut() {
+                        for evt in inputs.iter() {
+                            replay.record(state.status().frame, *evt);
+                        }
                     }
-                    Ok(Some(evt)) => {
-                        println!("timer evt: {:?}", evt);
-                        to_timer.send(evt).unwrap();
-                    }
-                    _ => (),
+                    tetrs::step(state, &inputs)
                 }
-            }
-        }
-    }
+            };
+            inputs.clear();
 
-    to_timer.send(tetrs::TimerEvent::Stop).unwrap();
-}
-
-/// Create a timer thread that sends tick events when the interval has elapsed
-fn create_timer(
-    tx: mpsc::Sender<tetrs::TimerEvent>,
-    receiver: mpsc::Receiver<tetrs::TimerEvent>,
-    mut interval: u128,
-) {
-    thread::spawn(move || {
-        let mut pause = false;
-        let mut last_time = time::Instant::now();
-
-        'timer: loop {
-            match receiver.try_recv() {
-                Err(mpsc::TryRecvError::Empty) => (),
-                Ok(tetrs::TimerEvent::Pause) => {
-                    pause = !pause;
-                }
-                Ok(tetrs::TimerEvent::SetInterval(i)) => {
-                    interval = i;
-                }
-                // exit on signal or sender disconnect
-                _ => {
-                    println!("timer exiting");
-                    break 'timer;
+            if let Err(e) = result {
+                println!("err: {}", e);
+            }
+            for event in events.read(state.ecs.events.get::<GameEvent>().unwrap()) {
+                if let GameEvent::GameOver(reason) = event {
+                    println!("game over: {:?}", reason);
                 }
             }
+        }
 
-            let now = time::Instant::now();
-            let dt = now.duration_since(last_time);
+        render.run(&mut screen).unwrap_or_else(panic_with_err);
+        gfx::display_frame(screen.canvas);
 
-            if !pause && dt.as_millis() >= interval {
-                last_time = now;
-                if tx.send(tetrs::TimerEvent::Tick).is_err() {
-                    break 'timer;
-                }
-            }
+        // don't spin while waiting for the next logic frame
+        thread::sleep(time::Duration::from_millis(1));
+    }
+
+    if let (Mode::Record(path), Some(replay)) = (mode, recording) {
+        match replay.save(&path) {
+            Ok(()) => println!("saved replay to {}", path),
+            Err(e) => println!("err: {}", e),
         }
-    });
+    }
 }
@@ -0,0 +1,203 @@
+//! Glass occupancy as bitboards
+//!
+//! Each row is a bitmask, with bit `x` set if column `x` is occupied. Checking if a row is full
+//! is a single compare, and a piece collides if any of its row masks, shifted to the piece's
+//! column, overlaps the row it lands on. Colors aren't kept here: locked cells stay entities, with
+//! their color, and are drawn from those.
+use serde::{Deserialize, Serialize};
+
+use crate::component::{Mesh, Position};
+
+/// Widest glass a matrix can hold
+pub const MAX_WIDTH: usize = u64::BITS as usize;
+
+/// Occupied cells of the glass, one bitmask per row, top row first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Matrix {
+    width: usize,
+    /// Mask with a bit set for each column
+    full: u64,
+    rows: Vec<u64>,
+}
+
+impl Matrix {
+    /// Create an empty matrix, `width` columns (at most `MAX_WIDTH`) by `height` rows
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(width <= MAX_WIDTH, "glass wider than {} columns", MAX_WIDTH);
+        let full = match width {
+            MAX_WIDTH => u64::MAX,
+            _ => (1 << width) - 1,
+        };
+
+        Self {
+            width,
+            full,
+            rows: vec![0; height],
+        }
+    }
+
+    /// Get the number of columns
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Get the number of rows
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Get the mask of occupied cells in row `y`
+    pub fn row(&self, y: usize) -> u64 {
+        self.rows[y]
+    }
+
+    /// Mark the cell at `pos` occupied. Cells outside the matrix are ignored
+    pub fn set(&mut self, pos: Position) {
+        if self.contains(pos) {
+            self.rows[pos.y as usize] |= 1 << pos.x;
+        }
+    }
+
+    /// Empty every cell
+    pub fn clear(&mut self) {
+        self.rows.iter_mut().for_each(|row| *row = 0);
+    }
+
+    /// True if `pos` is inside the matrix
+    fn contains(&self, pos: Position) -> bool {
+        pos.x >= 0
+            && pos.y >= 0
+            && (pos.x as usize) < self.width
+            && (pos.y as usize) < self.height()
+    }
+
+    /// Check if the cell at `pos` is outside the matrix or occupied
+    pub fn blocked(&self, pos: Position) -> bool {
+        !self.contains(pos) || self.rows[pos.y as usize] & (1 << pos.x) != 0
+    }
+
+    /// Check if every cell in row `y` is occupied
+    pub fn is_row_full(&self, y: usize) -> bool {
+        self.rows[y] == self.full
+    }
+
+    /// Check if no cell is occupied
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+
+    /// Get the full rows, bottom row first
+    pub fn full_rows(&self) -> Vec<usize> {
+        (0..self.height())
+            .rev()
+            .filter(|y| self.is_row_full(*y))
+            .collect()
+    }
+
+    /// Check if `piece` placed at `pos` would leave the matrix or overlap an occupied cell
+    pub fn collides(&self, piece: &PieceMask, pos: Position) -> bool {
+        let origin = pos + piece.offset;
+        piece.rows.iter().enumerate().any(|(i, mask)| {
+            let y = origin.y + i as i32;
+            if y < 0 || y as usize >= self.height() {
+                return *mask != 0;
+            }
+
+            match shift(*mask, origin.x) {
+                Some(mask) => mask & !self.full != 0 || mask & self.rows[y as usize] != 0,
+                None => true,
+            }
+        })
+    }
+}
+
+/// Shift a row mask `x` columns right, or None if cells would fall off either edge of a `u64`
+fn shift(mask: u64, x: i32) -> Option<u64> {
+    let (shifted, back) = if x < 0 {
+        let shifted = mask.checked_shr(x.unsigned_abs())?;
+        (shifted, shifted << x.unsigned_abs())
+    } else {
+        let shifted = mask.checked_shl(x as u32)?;
+        (shifted, shifted >> x)
+    };
+
+    // any cell that fell off the edge doesn't come back
+    (back == mask).then_some(shifted)
+}
+
+/// A piece's cells as row masks, relative to its top left cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct PieceMask {
+    /// Position of the top left cell in mesh space
+    offset: Position,
+    rows: Vec<u64>,
+}
+
+impl PieceMask {
+    /// Create the masks for `mesh`
+    pub fn new(mesh: &Mesh) -> Self {
+        let min_x = mesh.0.iter().map(|cell| cell.x).min().unwrap_or(0);
+        let min_y = mesh.0.iter().map(|cell| cell.y).min().unwrap_or(0);
+        let max_y = mesh.0.iter().map(|cell| cell.y).max().unwrap_or(-1);
+
+        let mut rows = vec![0; (max_y - min_y + 1) as usize];
+        for cell in mesh.0.iter() {
+            rows[(cell.y - min_y) as usize] |= 1 << (cell.x - min_x);
+        }
+
+        Self {
+            offset: Position { x: min_x, y: min_y },
+            rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Tetromino;
+
+    #[test]
+    fn row_full() {
+        let mut matrix = Matrix::new(4, 2);
+        for x in 0..4 {
+            matrix.set(Position { x, y: 1 });
+        }
+        matrix.set(Position { x: 0, y: 0 });
+
+        let expected = vec![1];
+        let actual = matrix.full_rows();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn collides_walls() {
+        let matrix = Matrix::new(10, 20);
+        let piece = PieceMask::new(&Tetromino::I.new());
+
+        // the I piece is 4 wide, so it fits from column 0 to 6
+        let expected = (true, false, false, true, true);
+        let actual = (
+            matrix.collides(&piece, Position { x: -1, y: 0 }),
+            matrix.collides(&piece, Position { x: 0, y: 0 }),
+            matrix.collides(&piece, Position { x: 6, y: 0 }),
+            matrix.collides(&piece, Position { x: 7, y: 0 }),
+            matrix.collides(&piece, Position { x: 0, y: 19 }),
+        );
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn collides_stack() {
+        let mut matrix = Matrix::new(10, 20);
+        matrix.set(Position { x: 5, y: 10 });
+        let piece = PieceMask::new(&Tetromino::O.new());
+
+        let expected = (true, false);
+        let actual = (
+            matrix.collides(&piece, Position { x: 4, y: 9 }),
+            matrix.collides(&piece, Position { x: 6, y: 9 }),
+        );
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,313 @@
+//! Piece randomizers
+//!
+//! A randomizer decides the order pieces are dealt in. Randomizers draw from the game's seeded
+//! rng, so the same seed always produces the same sequence. What a randomizer remembers between
+//! pieces is kept in the `History` resource, so it's saved with the rest of the game.
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, Rng, RngCore, SeedableRng};
+use rand_pcg::Pcg64;
+
+use serde::{de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Tetromino;
+
+/// Resource. The game's rng: `Pcg64`, a named algorithm, unlike `StdRng`, so a seed deals the
+/// same pieces whatever the rand version or platform
+#[derive(Debug, Clone)]
+pub struct GameRng(Pcg64);
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+impl SeedableRng for GameRng {
+    type Seed = <Pcg64 as SeedableRng>::Seed;
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self(Pcg64::from_seed(seed))
+    }
+
+    fn seed_from_u64(state: u64) -> Self {
+        Self(Pcg64::seed_from_u64(state))
+    }
+}
+
+/// `Pcg64`'s state, as it serializes itself
+#[derive(Serialize, Deserialize)]
+struct PcgState {
+    state: u128,
+    increment: u128,
+}
+
+/// `Pcg64`'s state as saved: snapshots can't hold u128s, so each is split into its high and low
+/// halves
+#[derive(Serialize, Deserialize)]
+struct SavedRng {
+    state: [u64; 2],
+    increment: [u64; 2],
+}
+
+fn split(value: u128) -> [u64; 2] {
+    [(value >> 64) as u64, value as u64]
+}
+
+fn join([high, low]: [u64; 2]) -> u128 {
+    (u128::from(high) << 64) | u128::from(low)
+}
+
+impl Serialize for GameRng {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Pcg64 keeps its state private, but JSON text holds u128s
+        let state: PcgState = serde_json::to_string(&self.0)
+            .and_then(|json| serde_json::from_str(&json))
+            .map_err(S::Error::custom)?;
+        SavedRng {
+            state: split(state.state),
+            increment: split(state.increment),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for GameRng {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let saved = SavedRng::deserialize(deserializer)?;
+        let state = PcgState {
+            state: join(saved.state),
+            increment: join(saved.increment),
+        };
+        serde_json::to_string(&state)
+            .and_then(|json| serde_json::from_str(&json))
+            .map(Self)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Resource. Pieces the randomizer remembers: the rest of the bag, or the last pieces dealt
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct History(pub VecDeque<Tetromino>);
+
+/// Source of upcoming pieces
+pub trait Randomizer {
+    /// Get the next piece, drawing any randomness from `rng` and updating `history`
+    fn next(&self, history: &mut History, rng: &mut dyn RngCore) -> Tetromino;
+}
+
+/// Available randomizers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RandomizerKind {
+    /// Deal shuffled bags of all 7 pieces
+    #[default]
+    SevenBag,
+    /// Deal shuffled bags of 2 of each piece
+    FourteenBag,
+    /// Every piece is equally likely, every time
+    PureRandom,
+    /// Roll once more if the roll repeats the previous piece, as in the NES game
+    NesReroll,
+    /// Roll up to `rolls` times while the roll is in the last 4 pieces, as in the TGM series
+    TgmHistory { rolls: u32 },
+}
+
+impl RandomizerKind {
+    /// Create a randomizer of this kind
+    pub fn create(self) -> Box<dyn Randomizer> {
+        match self {
+            Self::SevenBag => Box::new(Bag::new(1)),
+            Self::FourteenBag => Box::new(Bag::new(2)),
+            Self::PureRandom => Box::new(PureRandom),
+            Self::NesReroll => Box::new(NesReroll::new()),
+            Self::TgmHistory { rolls } => Box::new(TgmHistory::new(rolls)),
+        }
+    }
+}
+
+/// Deals every piece `copies` times, in random order, before starting over. The history is
+/// the rest of the bag
+pub struct Bag {
+    copies: usize,
+}
+
+impl Bag {
+    pub fn new(copies: usize) -> Self {
+        Self { copies }
+    }
+
+    /// Fill the bag and shuffle it
+    fn refill(&self, bag: &mut History, rng: &mut dyn RngCore) {
+        let mut pieces = Vec::new();
+        for _ in 0..self.copies {
+            pieces.extend_from_slice(&Tetromino::ALL);
+        }
+        pieces.shuffle(rng);
+        bag.0.extend(pieces);
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&self, history: &mut History, rng: &mut dyn RngCore) -> Tetromino {
+        if history.0.is_empty() {
+            self.refill(history, rng);
+        }
+        history.0.pop_back().unwrap()
+    }
+}
+
+/// Every piece is equally likely
+pub struct PureRandom;
+
+impl Randomizer for PureRandom {
+    fn next(&self, _history: &mut History, rng: &mut dyn RngCore) -> Tetromino {
+        Tetromino::random(rng)
+    }
+}
+
+/// NES randomizer. Rolls an 8 sided die, where the 8th side is a "reroll". If the roll is the
+/// reroll or repeats the previous piece, a 7 sided die is rolled once and its result is used.
+/// The history is the previous piece
+#[derive(Default)]
+pub struct NesReroll;
+
+impl NesReroll {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Randomizer for NesReroll {
+    fn next(&self, history: &mut History, rng: &mut dyn RngCore) -> Tetromino {
+        let roll = rng.gen_range(0, Tetromino::ALL.len() + 1);
+        let piece = match Tetromino::ALL.get(roll) {
+            Some(piece) if Some(piece) != history.0.back() => *piece,
+            _ => Tetromino::random(rng),
+        };
+
+        history.0.clear();
+        history.0.push_back(piece);
+        piece
+    }
+}
+
+/// TGM randomizer. Keeps a history of the last 4 pieces, and rolls up to `rolls` times to find a
+/// piece that isn't in it. The first piece is never S, Z or O.
+pub struct TgmHistory {
+    rolls: u32,
+}
+
+impl TgmHistory {
+    const HISTORY_LEN: usize = 4;
+    const FIRST_PIECES: [Tetromino; 4] = [Tetromino::I, Tetromino::J, Tetromino::L, Tetromino::T];
+
+    pub fn new(rolls: u32) -> Self {
+        Self { rolls }
+    }
+}
+
+impl Randomizer for TgmHistory {
+    fn next(&self, history: &mut History, rng: &mut dyn RngCore) -> Tetromino {
+        // the history starts out full of Z
+        let piece = if history.0.is_empty() {
+            history.0 = VecDeque::from(vec![Tetromino::Z; Self::HISTORY_LEN]);
+            *Self::FIRST_PIECES.choose(rng).unwrap()
+        } else {
+            let mut piece = Tetromino::random(rng);
+            for _ in 1..self.rolls {
+                if !history.0.contains(&piece) {
+                    break;
+                }
+                piece = Tetromino::random(rng);
+            }
+            piece
+        };
+
+        history.0.pop_front();
+        history.0.push_back(piece);
+        piece
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn deal(kind: RandomizerKind, seed: u64, count: usize) -> Vec<Tetromino> {
+        let mut rng = GameRng::seed_from_u64(seed);
+        let mut history = History::default();
+        let randomizer = kind.create();
+        (0..count)
+            .map(|_| randomizer.next(&mut history, &mut rng))
+            .collect()
+    }
+
+    #[test]
+    fn seeded_sequence() {
+        let kinds = [
+            RandomizerKind::SevenBag,
+            RandomizerKind::FourteenBag,
+            RandomizerKind::PureRandom,
+            RandomizerKind::NesReroll,
+            RandomizerKind::TgmHistory { rolls: 4 },
+        ];
+        for kind in kinds.iter() {
+            let expected = deal(*kind, 42, 50);
+            let actual = deal(*kind, 42, 50);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn stable_sequence() {
+        // seeds are part of the replay format: this sequence must not change
+        use Tetromino::*;
+        let expected = vec![S, I, O, L, Z, T, J];
+        let actual = deal(RandomizerKind::SevenBag, 0, 7);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn saved_rng() {
+        let mut rng = GameRng::seed_from_u64(3);
+        rng.next_u64();
+        let value = serde_json::to_value(&rng).unwrap();
+        let mut restored: GameRng = serde_json::from_value(value).unwrap();
+
+        let expected: Vec<u64> = (0..4).map(|_| rng.next_u64()).collect();
+        let actual: Vec<u64> = (0..4).map(|_| restored.next_u64()).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn seven_bag() {
+        let pieces = deal(RandomizerKind::SevenBag, 7, 70);
+        for bag in pieces.chunks(7) {
+            for kind in Tetromino::ALL.iter() {
+                let expected = 1;
+                let actual = bag.iter().filter(|piece| *piece == kind).count();
+                assert_eq!(actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn tgm_first_piece() {
+        for seed in 0..20 {
+            let actual = deal(RandomizerKind::TgmHistory { rolls: 4 }, seed, 1)[0];
+            assert!(TgmHistory::FIRST_PIECES.contains(&actual));
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Game recording and playback
+//!
+//! A replay holds everything needed to reproduce a game: the seed, the rules, and each input
+//! stamped with the frame it was applied on. Pieces are dealt from `randomizer::GameRng`, so
+//! changing the rng algorithm changes the format.
+//!
+//! Replays are saved as text, one item per line:
+//!
+//! ```text
+//! tetrs-replay 1
+//! seed 1234
+//! rotation SRS
+//! events
+//! 1 Left
+//! 18 HardDrop
+//! ```
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    score::{LevelGoal, ScoringKind},
+    Config, Error, GameState, InputEvent, LockReset, RandomizerKind, RotationKind, SoftDropFactor,
+    TetrsResult,
+};
+
+/// Version of the replay file format
+pub const REPLAY_VERSION: u32 = 1;
+
+const MAGIC: &str = "tetrs-replay";
+
+/// A recorded game
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub config: Config,
+    /// Inputs, and the frame they were applied on, in order
+    pub events: Vec<(u64, InputEvent)>,
+}
+
+impl Replay {
+    /// Create an empty replay for a game with `seed` and `config`
+    pub fn new(seed: u64, config: Config) -> Self {
+        Self {
+            seed,
+            config,
+            events: Vec::new(),
+        }
+    }
+
+    /// Create an empty replay for `state`
+    pub fn for_game(state: &GameState) -> Self {
+        Self::new(state.seed(), state.config().clone())
+    }
+
+    /// Create a game with the recorded seed and rules. Fails if the rules can't be played
+    pub fn new_game(&self) -> TetrsResult<GameState> {
+        GameState::with_seed(self.config.clone(), self.seed)
+    }
+
+    /// Add an input applied on `frame`
+    pub fn record(&mut self, frame: u64, evt: InputEvent) {
+        self.events.push((frame, evt));
+    }
+
+    /// Write the replay to `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> TetrsResult<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path).map_err(io_error)?);
+        self.write(&mut file).map_err(io_error)
+    }
+
+    /// Read a replay from `path`
+    pub fn load<P: AsRef<Path>>(path: P) -> TetrsResult<Self> {
+        let file = fs::File::open(path).map_err(io_error)?;
+        Self::read(io::BufReader::new(file))
+    }
+
+    /// Write the replay as text
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "{} {}", MAGIC, REPLAY_VERSION)?;
+        writeln!(w, "seed {}", self.seed)?;
+        write_config(w, &self.config)?;
+
+        writeln!(w, "events")?;
+        for (frame, evt) in self.events.iter() {
+            writeln!(w, "{} {:?}", frame, evt)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read a replay written by `write`
+    pub fn read<R: BufRead>(r: R) -> TetrsResult<Self> {
+        let mut lines = r.lines();
+        let mut next_line =
+            || -> TetrsResult<Option<String>> { lines.next().transpose().map_err(io_error) };
+
+        let header = next_line()?.ok_or_else(|| invalid("empty file"))?;
+        match header.split_whitespace().collect::<Vec<_>>()[..] {
+            [MAGIC, version] if parse::<u32>(version)? == REPLAY_VERSION => (),
+            [MAGIC, version] => return Err(invalid(&format!("unsupported version {}", version))),
+            _ => return Err(invalid("not a replay file")),
+        }
+
+        // settings
+        let mut replay = Self::new(0, Config::default());
+        loop {
+            let line = next_line()?.ok_or_else(|| invalid("missing events"))?;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                ["events"] => break,
+                ["seed", seed] => replay.seed = parse(seed)?,
+                [key, ref values @ ..] => read_config(&mut replay.config, key, values)?,
+                [] => (),
+            }
+        }
+
+        // events
+        while let Some(line) = next_line()? {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let evt = match words[..] {
+                [frame, input] => (parse(frame)?, parse_input(input)?),
+                [] => continue,
+                _ => return Err(invalid(&format!("bad event: {}", line))),
+            };
+            replay.events.push(evt);
+        }
+
+        Ok(replay)
+    }
+}
+
+/// Feeds a replay's events back into a game
+pub struct ReplayPlayer {
+    replay: Replay,
+    cursor: usize,
+}
+
+impl ReplayPlayer {
+    /// Create a new instance, positioned at the start of `replay`
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, cursor: 0 }
+    }
+
+    /// Get the replay being played
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// True if every event has been played
+    pub fn is_done(&self) -> bool {
+        self.cursor >= self.replay.events.len()
+    }
+
+    /// Step `state` by one frame, applying the inputs recorded for that frame
+    pub fn step(&mut self, state: &mut GameState) -> TetrsResult<()> {
+        let mut inputs = Vec::new();
+        while let Some(&(frame, evt)) = self.replay.events.get(self.cursor) {
+            if frame > state.status().frame {
+                break;
+            }
+            inputs.push(evt);
+            self.cursor += 1;
+        }
+
+        crate::step(state, &inputs)
+    }
+}
+
+fn io_error(err: io::Error) -> Error {
+    Error::ExternalString(err.to_string())
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::InvalidReplay(msg.to_string())
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> TetrsResult<T> {
+    value
+        .parse()
+        .map_err(|_| invalid(&format!("bad value: {}", value)))
+}
+
+fn parse_input(value: &str) -> TetrsResult<InputEvent> {
+    let input = match value {
+        "HardDrop" => InputEvent::HardDrop,
+        "Hold" => InputEvent::Hold,
+        "Left" => InputEvent::Left,
+        "LeftEnd" => InputEvent::LeftEnd,
+        "Pause" => InputEvent::Pause,
+        "Right" => InputEvent::Right,
+        "RightEnd" => InputEvent::RightEnd,
+        "RotateCCW" => InputEvent::RotateCCW,
+        "RotateCW" => InputEvent::RotateCW,
+        "SoftDrop" => InputEvent::SoftDrop,
+        "SoftDropEnd" => InputEvent::SoftDropEnd,
+        _ => return Err(invalid(&format!("bad input: {}", value))),
+    };
+    Ok(input)
+}
+
+/// Write each rule in `config` as a `key values...` line
+fn write_config<W: Write>(w: &mut W, config: &Config) -> io::Result<()> {
+    writeln!(w, "rotation {:?}", config.rotation)?;
+    match config.randomizer {
+        RandomizerKind::TgmHistory { rolls } => writeln!(w, "randomizer TgmHistory {}", rolls)?,
+        randomizer => writeln!(w, "randomizer {:?}", randomizer)?,
+    }
+    let lock_delay = config.lock_delay;
+    writeln!(
+        w,
+        "lock_delay {} {:?} {}",
+        lock_delay.delay, lock_delay.reset, lock_delay.max_resets
+    )?;
+    match config.soft_drop {
+        SoftDropFactor::Factor(factor) => writeln!(w, "soft_drop Factor {}", factor)?,
+        SoftDropFactor::Instant => writeln!(w, "soft_drop Instant")?,
+    }
+    let auto_shift = config.auto_shift;
+    writeln!(
+        w,
+        "auto_shift {} {} {}",
+        auto_shift.das, auto_shift.arr, auto_shift.cut
+    )?;
+    writeln!(w, "ghost {}", config.ghost)?;
+    match config.gravity {
+        Some(g) => writeln!(w, "gravity {}", g)?,
+        None => writeln!(w, "gravity level")?,
+    }
+    match config.scoring {
+        ScoringKind::Guideline { goal } => writeln!(w, "scoring Guideline {:?}", goal)?,
+        ScoringKind::Nes { start_level } => writeln!(w, "scoring Nes {}", start_level)?,
+        ScoringKind::Tgm => writeln!(w, "scoring Tgm")?,
+    }
+    writeln!(w, "hold {}", config.hold)?;
+    writeln!(w, "size {} {}", config.width, config.height)?;
+    writeln!(w, "hidden_rows {}", config.hidden_rows)?;
+    let entry_delay = config.entry_delay;
+    writeln!(
+        w,
+        "entry_delay {} {}",
+        entry_delay.are, entry_delay.line_clear
+    )?;
+
+    Ok(())
+}
+
+/// Set the rule named `key` in `config` from a line written by `write_config`
+fn read_config(config: &mut Config, key: &str, values: &[&str]) -> TetrsResult<()> {
+    match (key, values) {
+        ("rotation", ["SRS"]) => config.rotation = RotationKind::SRS,
+        ("rotation", ["NRS"]) => config.rotation = RotationKind::NRS,
+        ("rotation", ["ARS"]) => config.rotation = RotationKind::ARS,
+        ("randomizer", ["SevenBag"]) => config.randomizer = RandomizerKind::SevenBag,
+        ("randomizer", ["FourteenBag"]) => config.randomizer = RandomizerKind::FourteenBag,
+        ("randomizer", ["PureRandom"]) => config.randomizer = RandomizerKind::PureRandom,
+        ("randomizer", ["NesReroll"]) => config.randomizer = RandomizerKind::NesReroll,
+        ("randomizer", ["TgmHistory", rolls]) => {
+            config.randomizer = RandomizerKind::TgmHistory {
+                rolls: parse(rolls)?,
+            }
+        }
+        ("lock_delay", [delay, reset, max_resets]) => {
+            config.lock_delay.delay = parse(delay)?;
+            config.lock_delay.reset = match *reset {
+                "Move" => LockReset::Move,
+                "Step" => LockReset::Step,
+                "Infinity" => LockReset::Infinity,
+                _ => return Err(invalid(&format!("bad lock reset: {}", reset))),
+            };
+            config.lock_delay.max_resets = parse(max_resets)?;
+        }
+        ("soft_drop", ["Factor", factor]) => {
+            config.soft_drop = SoftDropFactor::Factor(parse(factor)?)
+        }
+        ("soft_drop", ["Instant"]) => config.soft_drop = SoftDropFactor::Instant,
+        ("auto_shift", [das, arr, cut]) => {
+            config.auto_shift.das = parse(das)?;
+            config.auto_shift.arr = parse(arr)?;
+            config.auto_shift.cut = parse(cut)?;
+        }
+        ("ghost", [ghost]) => config.ghost = parse(ghost)?,
+        ("gravity", ["level"]) => config.gravity = None,
+        ("gravity", [g]) => config.gravity = Some(parse(g)?),
+        ("scoring", ["Guideline", goal]) => {
+            let goal = match *goal {
+                "Fixed" => LevelGoal::Fixed,
+                "Variable" => LevelGoal::Variable,
+                _ => return Err(invalid(&format!("bad level goal: {}", goal))),
+            };
+            config.scoring = ScoringKind::Guideline { goal };
+        }
+        ("scoring", ["Nes", start_level]) => {
+            config.scoring = ScoringKind::Nes {
+                start_level: parse(start_level)?,
+            }
+        }
+        ("scoring", ["Tgm"]) => config.scoring = ScoringKind::Tgm,
+        ("hold", [hold]) => config.hold = parse(hold)?,
+        ("size", [width, height]) => {
+            config.width = parse(width)?;
+            config.height = parse(height)?;
+        }
+        ("hidden_rows", [rows]) => config.hidden_rows = parse(rows)?,
+        ("entry_delay", [are, line_clear]) => {
+            config.entry_delay.are = parse(are)?;
+            config.entry_delay.line_clear = parse(line_clear)?;
+        }
+        _ => return Err(invalid(&format!("bad setting: {} {:?}", key, values))),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn write_read() {
+        let config = Config {
+            rotation: RotationKind::ARS,
+            randomizer: RandomizerKind::TgmHistory { rolls: 6 },
+            soft_drop: SoftDropFactor::Instant,
+            ghost: false,
+            gravity: Some(crate::MAX_GRAVITY),
+            scoring: ScoringKind::Nes { start_level: 18 },
+            hold: false,
+            width: 4,
+            height: 40,
+            hidden_rows: 2,
+            entry_delay: crate::EntryDelay::none(),
+            ..Config::default()
+        };
+
+        let mut expected = Replay::new(1234, config);
+        expected.record(0, InputEvent::Left);
+        expected.record(1, InputEvent::RotateCCW);
+        expected.record(12, InputEvent::LeftEnd);
+        expected.record(18, InputEvent::HardDrop);
+
+        let mut buf = Vec::new();
+        expected.write(&mut buf).unwrap();
+        let actual = Replay::read(&buf[..]).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn read_version() {
+        let actual = Replay::read(&b"tetrs-replay 999\nevents\n"[..]);
+        assert!(actual.is_err());
+    }
+}
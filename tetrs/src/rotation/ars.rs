@@ -0,0 +1,122 @@
+//! Arika Rotation System (ARS)
+use super::{srs, RotationState, RotationSystem};
+use crate::{
+    component::{Mesh, Position},
+    Tetromino,
+};
+
+/// Arika Rotation System, as in the TGM series.
+///
+/// Pieces spawn flat side up and rest on the bottom of their bounding box. When a rotation is
+/// obstructed, a kick one cell right and then one cell left is tried. I never kicks.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Ars;
+
+const DOWN: Position = Position { x: 0, y: 1 };
+
+/// Check the center column rule for J, L and T: if the first obstructed cell, in reading order,
+/// is in the center column the rotation may not kick.
+fn center_column_blocked(obstructed: &[Position]) -> bool {
+    obstructed
+        .iter()
+        .min_by_key(|cell| (cell.y, cell.x))
+        .is_some_and(|cell| cell.x == 1)
+}
+
+impl RotationSystem for Ars {
+    fn mesh(&self, kind: Tetromino, state: RotationState) -> Mesh {
+        let mut mesh = match kind {
+            Tetromino::O => srs::mesh(kind, RotationState::Zero),
+            Tetromino::I => match state {
+                RotationState::Zero | RotationState::Two => srs::mesh(kind, RotationState::Zero),
+                RotationState::Right | RotationState::Left => srs::mesh(kind, RotationState::Right),
+            },
+            Tetromino::S | Tetromino::Z => match state {
+                RotationState::Zero | RotationState::Two => srs::mesh(kind, RotationState::Two),
+                // S is left-handed, Z is right-handed
+                RotationState::Right | RotationState::Left if kind == Tetromino::S => {
+                    srs::mesh(kind, RotationState::Left)
+                }
+                RotationState::Right | RotationState::Left => srs::mesh(kind, RotationState::Right),
+            },
+            _ => srs::mesh(kind, state.flip()),
+        };
+
+        // flat side down orientations drop to the bottom of the bounding box
+        let bottom_aligned = match kind {
+            Tetromino::O => true,
+            Tetromino::J | Tetromino::L | Tetromino::T => state == RotationState::Two,
+            _ => false,
+        };
+        if bottom_aligned {
+            mesh.translate(DOWN);
+        }
+
+        mesh
+    }
+
+    fn kicks(
+        &self,
+        kind: Tetromino,
+        _from: RotationState,
+        _to: RotationState,
+        obstructed: &[Position],
+    ) -> Vec<Position> {
+        const NONE: Position = Position { x: 0, y: 0 };
+        const RIGHT: Position = Position { x: 1, y: 0 };
+        const LEFT: Position = Position { x: -1, y: 0 };
+
+        match kind {
+            Tetromino::I => vec![NONE],
+            Tetromino::J | Tetromino::L | Tetromino::T if center_column_blocked(obstructed) => {
+                vec![NONE]
+            }
+            _ => vec![NONE, RIGHT, LEFT],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mesh_two() {
+        let expected = vec![
+            Position { x: 0, y: 2 },
+            Position { x: 1, y: 2 },
+            Position { x: 2, y: 2 },
+            Position { x: 1, y: 1 },
+        ];
+        let actual = Vec::from(Ars.mesh(Tetromino::T, RotationState::Two).0);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn kicks_center_column() {
+        {
+            let obstructed = [Position { x: 1, y: 0 }, Position { x: 0, y: 1 }];
+            let expected = vec![Position { x: 0, y: 0 }];
+            let actual = Ars.kicks(
+                Tetromino::T,
+                RotationState::Zero,
+                RotationState::Right,
+                &obstructed,
+            );
+            assert_eq!(actual, expected);
+        }
+        {
+            let obstructed = [Position { x: 0, y: 1 }, Position { x: 1, y: 2 }];
+            let expected = 3;
+            let actual = Ars
+                .kicks(
+                    Tetromino::T,
+                    RotationState::Zero,
+                    RotationState::Right,
+                    &obstructed,
+                )
+                .len();
+            assert_eq!(actual, expected);
+        }
+    }
+}
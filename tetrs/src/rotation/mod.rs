@@ -0,0 +1,141 @@
+//! Rotation systems
+//!
+//! A rotation system decides what a tetromino looks like in each rotation state, which state a
+//! piece spawns in, and which translations ("kicks") are tried when a rotation is obstructed.
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    component::{Mesh, Position},
+    RotationDirection, Tetromino,
+};
+
+mod ars;
+mod nrs;
+mod srs;
+
+pub use ars::Ars;
+pub use nrs::Nrs;
+pub use srs::Srs;
+
+/// Rotation states of a tetromino, named as in the guideline: spawn (0), right (R), two
+/// successive rotations from spawn (2), and left (L)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RotationState {
+    #[default]
+    Zero,
+    Right,
+    Two,
+    Left,
+}
+
+impl RotationState {
+    /// Get the state reached by rotating once in `direction`
+    pub fn rotate(self, direction: RotationDirection) -> Self {
+        match (self, direction) {
+            (Self::Zero, RotationDirection::CW) => Self::Right,
+            (Self::Right, RotationDirection::CW) => Self::Two,
+            (Self::Two, RotationDirection::CW) => Self::Left,
+            (Self::Left, RotationDirection::CW) => Self::Zero,
+            (Self::Zero, RotationDirection::CCW) => Self::Left,
+            (Self::Left, RotationDirection::CCW) => Self::Two,
+            (Self::Two, RotationDirection::CCW) => Self::Right,
+            (Self::Right, RotationDirection::CCW) => Self::Zero,
+        }
+    }
+
+    /// Get the state reached by rotating twice
+    pub fn flip(self) -> Self {
+        self.rotate(RotationDirection::CW)
+            .rotate(RotationDirection::CW)
+    }
+}
+
+/// Rules for rotating tetrominoes
+pub trait RotationSystem {
+    /// Get the rotation state `kind` spawns in
+    fn spawn_state(&self, _kind: Tetromino) -> RotationState {
+        RotationState::Zero
+    }
+
+    /// Get the mesh for `kind` in the rotation `state`
+    fn mesh(&self, kind: Tetromino, state: RotationState) -> Mesh;
+
+    /// Get the state reached by rotating `kind` in `direction`, or `None` if the piece doesn't
+    /// rotate at all
+    fn rotate(
+        &self,
+        kind: Tetromino,
+        state: RotationState,
+        direction: RotationDirection,
+    ) -> Option<RotationState> {
+        match kind {
+            Tetromino::O => None,
+            _ => Some(state.rotate(direction)),
+        }
+    }
+
+    /// Get the translations to test, in order, when rotating `kind` from `from` to `to`. The
+    /// first translation that doesn't collide is used.
+    ///
+    /// `obstructed` lists the cells of the rotated mesh, in mesh space, that collide before any
+    /// translation is applied.
+    fn kicks(
+        &self,
+        kind: Tetromino,
+        from: RotationState,
+        to: RotationState,
+        obstructed: &[Position],
+    ) -> Vec<Position>;
+
+    /// Get the mesh for `kind` in its spawn orientation
+    fn spawn_mesh(&self, kind: Tetromino) -> Mesh {
+        self.mesh(kind, self.spawn_state(kind))
+    }
+}
+
+/// Available rotation systems
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RotationKind {
+    /// Super Rotation System, as used by guideline games
+    #[default]
+    SRS,
+    /// Nintendo Rotation System, as used by the NES game. No kicks
+    NRS,
+    /// Arika Rotation System, as used by the TGM series
+    ARS,
+}
+
+impl RotationKind {
+    /// Create the rotation system for this kind
+    pub fn system(self) -> Box<dyn RotationSystem> {
+        match self {
+            Self::SRS => Box::new(Srs),
+            Self::NRS => Box::new(Nrs),
+            Self::ARS => Box::new(Ars),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rotate_state() {
+        {
+            let expected = RotationState::Right;
+            let actual = RotationState::Zero.rotate(RotationDirection::CW);
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = RotationState::Left;
+            let actual = RotationState::Zero.rotate(RotationDirection::CCW);
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = RotationState::Two;
+            let actual = RotationState::Zero.flip();
+            assert_eq!(actual, expected);
+        }
+    }
+}
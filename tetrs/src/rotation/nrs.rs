@@ -0,0 +1,53 @@
+//! Nintendo Rotation System (NRS)
+use super::{srs, RotationState, RotationSystem};
+use crate::{
+    component::{Mesh, Position},
+    Tetromino,
+};
+
+/// Nintendo Rotation System, as in the NES game.
+///
+/// Pieces spawn flat side up and never kick. I, S and Z only have two orientations, and are
+/// right-handed: their vertical orientation sits right of center.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Nrs;
+
+impl RotationSystem for Nrs {
+    fn mesh(&self, kind: Tetromino, state: RotationState) -> Mesh {
+        match kind {
+            Tetromino::O => srs::mesh(kind, RotationState::Zero),
+            Tetromino::I | Tetromino::S | Tetromino::Z => match state {
+                RotationState::Zero | RotationState::Two => srs::mesh(kind, RotationState::Two),
+                RotationState::Right | RotationState::Left => srs::mesh(kind, RotationState::Right),
+            },
+            _ => srs::mesh(kind, state.flip()),
+        }
+    }
+
+    fn kicks(
+        &self,
+        _kind: Tetromino,
+        _from: RotationState,
+        _to: RotationState,
+        _obstructed: &[Position],
+    ) -> Vec<Position> {
+        vec![Position { x: 0, y: 0 }]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mesh_spawn() {
+        let expected = vec![
+            Position { x: 2, y: 1 },
+            Position { x: 1, y: 1 },
+            Position { x: 0, y: 1 },
+            Position { x: 1, y: 2 },
+        ];
+        let actual = Vec::from(Nrs.spawn_mesh(Tetromino::T).0);
+        assert_eq!(actual, expected);
+    }
+}
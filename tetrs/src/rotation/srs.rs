@@ -0,0 +1,150 @@
+//! Super Rotation System (SRS)
+use super::{RotationState, RotationSystem};
+use crate::{
+    component::{Mesh, Position},
+    Tetromino,
+};
+
+/// Super Rotation System. Pieces rotate within their bounding box and try up to five kicks
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Srs;
+
+type KickTable = [[(i32, i32); 5]; 8];
+
+/// Wall kick tests for J, L, S, T and Z, one row per transition (see `transition_index`).
+///
+/// Offsets use the guideline notation where positive y is up
+const JLSTZ_KICKS: KickTable = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 0 -> R
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 0
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 2
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 2 -> R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 2 -> L
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 2
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 0
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 0 -> L
+];
+
+/// Wall kick tests for I, one row per transition (see `transition_index`).
+///
+/// Offsets use the guideline notation where positive y is up
+const I_KICKS: KickTable = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // 0 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // R -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // R -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // 2 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // 2 -> L
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // L -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // L -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // 0 -> L
+];
+
+/// Row of the kick tables for a rotation from `from` to `to`
+fn transition_index(from: RotationState, to: RotationState) -> Option<usize> {
+    use RotationState::*;
+
+    match (from, to) {
+        (Zero, Right) => Some(0),
+        (Right, Zero) => Some(1),
+        (Right, Two) => Some(2),
+        (Two, Right) => Some(3),
+        (Two, Left) => Some(4),
+        (Left, Two) => Some(5),
+        (Left, Zero) => Some(6),
+        (Zero, Left) => Some(7),
+        _ => None,
+    }
+}
+
+/// Get the SRS mesh for `kind` in the rotation `state`, by rotating the spawn mesh within its
+/// bounding box
+pub(super) fn mesh(kind: Tetromino, state: RotationState) -> Mesh {
+    let size = kind.size();
+    let mesh = kind.new();
+
+    match state {
+        RotationState::Zero => mesh,
+        RotationState::Right => mesh.rotate_cw(size),
+        RotationState::Two => mesh.rotate_cw(size).rotate_cw(size),
+        RotationState::Left => mesh.rotate_ccw(size),
+    }
+}
+
+impl RotationSystem for Srs {
+    fn mesh(&self, kind: Tetromino, state: RotationState) -> Mesh {
+        mesh(kind, state)
+    }
+
+    /// Offsets are in glass space (positive y is down)
+    fn kicks(
+        &self,
+        kind: Tetromino,
+        from: RotationState,
+        to: RotationState,
+        _obstructed: &[Position],
+    ) -> Vec<Position> {
+        let table = match kind {
+            Tetromino::O => return Vec::new(),
+            Tetromino::I => &I_KICKS,
+            _ => &JLSTZ_KICKS,
+        };
+
+        transition_index(from, to)
+            .map(|i| {
+                table[i]
+                    .iter()
+                    .map(|&(x, y)| Position { x, y: -y })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mesh_right() {
+        let expected = vec![
+            Position { x: 1, y: 0 },
+            Position { x: 1, y: 1 },
+            Position { x: 1, y: 2 },
+            Position { x: 2, y: 1 },
+        ];
+        let actual = Vec::from(mesh(Tetromino::T, RotationState::Right).0);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn kicks_jlstz() {
+        let expected = vec![
+            Position { x: 0, y: 0 },
+            Position { x: -1, y: 0 },
+            Position { x: -1, y: -1 },
+            Position { x: 0, y: 2 },
+            Position { x: -1, y: 2 },
+        ];
+        let actual = Srs.kicks(Tetromino::T, RotationState::Zero, RotationState::Right, &[]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn kicks_i() {
+        let expected = vec![
+            Position { x: 0, y: 0 },
+            Position { x: -1, y: 0 },
+            Position { x: 2, y: 0 },
+            Position { x: -1, y: -2 },
+            Position { x: 2, y: 1 },
+        ];
+        let actual = Srs.kicks(Tetromino::I, RotationState::Zero, RotationState::Left, &[]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn kicks_o() {
+        let actual = Srs.kicks(Tetromino::O, RotationState::Zero, RotationState::Right, &[]);
+        assert!(actual.is_empty());
+    }
+}
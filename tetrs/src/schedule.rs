@@ -0,0 +1,268 @@
+//! The systems run for each frame of game logic
+//!
+//! `step` runs the game's schedule once per frame. Rules are added as systems in one of the
+//! stages, so they don't need changes to the game loop.
+use ecs::{types::Cell, Access, Schedule, System};
+
+use crate::{
+    component::*,
+    input::{self, AutoShiftState},
+    lock::LockState,
+    matrix::Matrix,
+    randomizer::{GameRng, History},
+    score, Error, GameState, Phase, TetrsResult,
+};
+
+/// Stage names, in run order
+pub mod stage {
+    /// Apply the player's inputs
+    pub const INPUT: &str = "input";
+    /// Move the current piece: auto shift, gravity and lock delay
+    pub const SIMULATE: &str = "simulate";
+    /// Update the state that follows pieces locking, such as scoring
+    pub const POST_LOCK: &str = "post_lock";
+    /// Draw the game. Only the frontend's render schedule has this stage
+    pub const RENDER: &str = "render";
+}
+
+/// Create the schedule `step` runs each frame
+pub fn logic_schedule() -> Schedule<GameState, Error> {
+    let mut schedule = Schedule::new();
+    schedule
+        .add_stage(stage::INPUT)
+        .add_stage(stage::SIMULATE)
+        .add_stage(stage::POST_LOCK)
+        .add_system(stage::INPUT, TickPhase)
+        .add_system(stage::INPUT, ApplyInputs)
+        .add_system(stage::SIMULATE, ApplyAutoShift)
+        .add_system(stage::SIMULATE, ApplyGravity)
+        .add_system(stage::SIMULATE, ApplyLockDelay)
+        .add_system(stage::POST_LOCK, UpdateScoring);
+    schedule
+}
+
+/// Add what checking the game is running reads
+fn runs(access: Access) -> Access {
+    access.read::<Status>()
+}
+
+/// Add what moving the current piece touches: moves restart the lock delay, and forget the last
+/// rotation
+fn moves(access: Access) -> Access {
+    access
+        .read::<CurrentPiece>()
+        .read::<Mesh>()
+        .read::<Matrix>()
+        .write::<Position>()
+        .write::<LockState>()
+        .write::<PieceState>()
+}
+
+/// Add what locking the current piece touches: the piece breaks into single cells, lines clear
+/// and score, the line clear delay starts, and the next piece may spawn, dealing a new preview
+/// or ending the game
+fn locks(access: Access) -> Access {
+    access
+        .write::<Cell>()
+        .write::<Mesh>()
+        .write::<Size>()
+        .write::<Kind>()
+        .write::<Orientation>()
+        .write::<Position>()
+        .write::<Gravity>()
+        .write::<Player>()
+        .write::<Preview>()
+        .write::<Matrix>()
+        .write::<Scoring>()
+        .write::<PieceQueue>()
+        .write::<CurrentPiece>()
+        .write::<LockState>()
+        .write::<PieceState>()
+        .write::<Phase>()
+        .write::<Status>()
+        .write::<GameRng>()
+        .write::<History>()
+}
+
+/// True if the game is neither paused nor over
+fn running(state: &GameState) -> bool {
+    !state.status().paused && !state.status().game_over
+}
+
+/// Count a frame of the line clear or entry delay. A piece locking doesn't count toward the
+/// delays that follow it, so this runs before the inputs
+pub struct TickPhase;
+
+impl System<GameState, Error> for TickPhase {
+    fn name(&self) -> &'static str {
+        "tick_phase"
+    }
+
+    fn access(&self) -> Access {
+        runs(Access::new()).write::<Phase>()
+    }
+
+    fn before(&self) -> Vec<&'static str> {
+        vec!["apply_inputs"]
+    }
+
+    fn run(&mut self, state: &mut GameState) -> TetrsResult<()> {
+        if running(state) {
+            state.phase_mut().tick();
+        }
+        Ok(())
+    }
+}
+
+/// Apply the inputs queued for this frame, in order
+pub struct ApplyInputs;
+
+impl System<GameState, Error> for ApplyInputs {
+    fn name(&self) -> &'static str {
+        "apply_inputs"
+    }
+
+    fn access(&self) -> Access {
+        // hard drops lock, holds swap the current piece, and pausing changes the status
+        locks(moves(Access::new()))
+            .read::<Kind>()
+            .write::<Orientation>()
+            .write::<Gravity>()
+            .write::<Hold>()
+            .write::<HoldPiece>()
+            .write::<AutoShiftState>()
+    }
+
+    fn run(&mut self, state: &mut GameState) -> TetrsResult<()> {
+        for input in std::mem::take(&mut state.inputs) {
+            input::on_input_event(state, input);
+        }
+        Ok(())
+    }
+}
+
+/// Move the current piece while Left or Right is held
+pub struct ApplyAutoShift;
+
+impl System<GameState, Error> for ApplyAutoShift {
+    fn name(&self) -> &'static str {
+        "auto_shift"
+    }
+
+    fn access(&self) -> Access {
+        runs(moves(Access::new())).write::<AutoShiftState>()
+    }
+
+    fn run(&mut self, state: &mut GameState) -> TetrsResult<()> {
+        if !running(state) {
+            return Ok(());
+        }
+        input::update_auto_shift(state)
+    }
+}
+
+/// Move the current piece down for a frame of gravity, or move on from the line clear or entry
+/// delay if no piece is in play
+pub struct ApplyGravity;
+
+impl System<GameState, Error> for ApplyGravity {
+    fn name(&self) -> &'static str {
+        "gravity"
+    }
+
+    fn access(&self) -> Access {
+        // soft drops score, pieces lock on contact without a lock delay, and the line clear and
+        // entry delays end here
+        runs(locks(moves(Access::new())))
+            .read::<Gravity>()
+            .write::<Scoring>()
+    }
+
+    fn after(&self) -> Vec<&'static str> {
+        vec!["auto_shift"]
+    }
+
+    fn run(&mut self, state: &mut GameState) -> TetrsResult<()> {
+        if !running(state) {
+            return Ok(());
+        }
+        if *state.phase() == Phase::Falling {
+            crate::apply_gravity(state)
+        } else {
+            crate::advance_phase(state)
+        }
+    }
+}
+
+/// Count down the lock delay of a grounded piece, and lock it when it runs out
+pub struct ApplyLockDelay;
+
+impl System<GameState, Error> for ApplyLockDelay {
+    fn name(&self) -> &'static str {
+        "lock_delay"
+    }
+
+    fn access(&self) -> Access {
+        runs(locks(Access::new())).read::<Matrix>()
+    }
+
+    fn after(&self) -> Vec<&'static str> {
+        vec!["gravity"]
+    }
+
+    fn run(&mut self, state: &mut GameState) -> TetrsResult<()> {
+        if !running(state) || *state.phase() != Phase::Falling {
+            return Ok(());
+        }
+        crate::update_lock_delay(state)
+    }
+}
+
+/// Update the scoring state for the frame
+pub struct UpdateScoring;
+
+impl System<GameState, Error> for UpdateScoring {
+    fn name(&self) -> &'static str {
+        "scoring"
+    }
+
+    fn access(&self) -> Access {
+        runs(Access::new()).write::<Scoring>()
+    }
+
+    fn run(&mut self, state: &mut GameState) -> TetrsResult<()> {
+        if !running(state) {
+            return Ok(());
+        }
+        score::on_frame(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn logic_order() {
+        let mut schedule = logic_schedule();
+
+        let expected = vec!["auto_shift", "gravity", "lock_delay"];
+        let actual = schedule.systems(stage::SIMULATE).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn no_ambiguities() {
+        let mut schedule = logic_schedule();
+
+        // the simulate systems all move the current piece, so only their ordering keeps them
+        // from being ambiguous
+        assert!(ApplyAutoShift.access().conflicts(&ApplyGravity.access()));
+        assert!(ApplyGravity.access().conflicts(&ApplyLockDelay.access()));
+        for stage in schedule.stages() {
+            let expected: Vec<(&str, &str)> = Vec::new();
+            let actual = schedule.ambiguities(stage).unwrap();
+            assert_eq!(actual, expected, "stage {}", stage);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Modern guideline scoring
+use serde::{Deserialize, Serialize};
+
+use super::{Clear, ScoringRules};
+use crate::{component::Scoring, GameEvent, TSpin};
+/// How many lines it takes to level up
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LevelGoal {
+    /// Every 10 lines
+    #[default]
+    Fixed,
+    /// 5 times the level, in line credits. Harder clears are worth more credits, e.g. 8 for a
+    /// tetris
+    Variable,
+}
+
+/// Guideline scoring: T-spins, back-to-back, combos and all clears, with guideline gravity
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Guideline {
+    pub goal: LevelGoal,
+}
+
+/// Gravity, in 1/65536 G, for levels 1 to 20. A row takes (0.8 - (level - 1) * 0.007) ^ (level
+/// - 1) seconds to fall
+const GRAVITY: [u32; 20] = [
+    1092, 1377, 1768, 2311, 3075, 4169, 5759, 8107, 11634, 17026, 25416, 38709, 60169, 95483,
+    154742, 256187, 433425, 749597, 1310720, 1310720,
+];
+
+/// Points per combo step
+const COMBO_POINTS: u32 = 50;
+
+/// Get the points for a clear of `line_count` lines by a piece that locked with `tspin`, before
+/// the level multiplier
+fn action_points(line_count: usize, tspin: TSpin) -> u32 {
+    const POINT_MAP: [u32; 5] = [0, 100, 300, 500, 800];
+    const MINI_POINT_MAP: [u32; 3] = [100, 200, 400];
+    const TSPIN_POINT_MAP: [u32; 4] = [400, 800, 1200, 1600];
+
+    let point_map: &[u32] = match tspin {
+        TSpin::None => &POINT_MAP,
+        TSpin::Mini => &MINI_POINT_MAP,
+        TSpin::Full => &TSPIN_POINT_MAP,
+    };
+
+    point_map.get(line_count).copied().unwrap_or(0)
+}
+
+/// Calculate tetris score for a `line_count` cleared lines by a piece that locked with `tspin`,
+/// at the current `level`
+fn calc_score(line_count: usize, tspin: TSpin, level: u32) -> u32 {
+    action_points(line_count, tspin) * (level + 1)
+}
+
+/// Calculate the all clear bonus for `line_count` cleared lines at the current `level`
+fn calc_all_clear(line_count: usize, back_to_back: bool, level: u32) -> u32 {
+    const POINT_MAP: [u32; 5] = [0, 800, 1200, 1800, 2000];
+    const B2B_TETRIS_POINTS: u32 = 3200;
+
+    let points = match line_count {
+        4 if back_to_back => B2B_TETRIS_POINTS,
+        _ => POINT_MAP.get(line_count).copied().unwrap_or(0),
+    };
+
+    points * (level + 1)
+}
+
+impl ScoringRules for Guideline {
+    fn gravity(&self, level: u32) -> u32 {
+        GRAVITY[std::cmp::min(level as usize, GRAVITY.len() - 1)]
+    }
+
+    fn award(&self, scoring: &mut Scoring, clear: Clear) -> Vec<GameEvent> {
+        let Clear {
+            lines: line_count,
+            tspin,
+            all_clear,
+        } = clear;
+        let mut events = Vec::new();
+        let level = scoring.level;
+        let mut points = calc_score(line_count, tspin, level);
+        let mut credits = action_points(line_count, tspin) / 100;
+
+        if line_count > 0 {
+            // tetrises and T-spins with lines are difficult; back to back, they're worth half
+            // again
+            let difficult = line_count == 4 || tspin != TSpin::None;
+            if difficult && scoring.back_to_back {
+                points = points * 3 / 2;
+                credits = credits * 3 / 2;
+                events.push(GameEvent::BackToBack);
+            }
+
+            let combo = scoring.combo.map_or(0, |combo| combo + 1);
+            if combo > 0 {
+                points += COMBO_POINTS * combo * (level + 1);
+                events.push(GameEvent::Combo(combo));
+            }
+
+            if all_clear {
+                points += calc_all_clear(line_count, difficult && scoring.back_to_back, level);
+                events.push(GameEvent::AllClear(line_count));
+            }
+
+            scoring.combo = Some(combo);
+            scoring.back_to_back = difficult;
+        } else {
+            scoring.combo = None;
+        }
+
+        // gravity follows the level, so leveling up speeds up the game
+        scoring.score += points;
+        scoring.lines += line_count as u32;
+        match self.goal {
+            LevelGoal::Fixed => scoring.level = scoring.lines / 10,
+            LevelGoal::Variable => {
+                scoring.progress += credits;
+                while scoring.progress >= 5 * (scoring.level + 1) {
+                    scoring.progress -= 5 * (scoring.level + 1);
+                    scoring.level += 1;
+                }
+            }
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Clear `lines` lines, without a T-spin or all clear
+    fn lines(lines: usize) -> Clear {
+        Clear {
+            lines,
+            ..Clear::default()
+        }
+    }
+
+    #[test]
+    fn calc_score_invalid() {
+        {
+            let expected = 0;
+            let actual = calc_score(5, TSpin::None, 0);
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = 0;
+            let actual = calc_score(0, TSpin::None, 0);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn calc_score_by_line() {
+        {
+            let expected = 100;
+            let actual = calc_score(1, TSpin::None, 0);
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = 300;
+            let actual = calc_score(2, TSpin::None, 0);
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = 500;
+            let actual = calc_score(3, TSpin::None, 0);
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = 800;
+            let actual = calc_score(4, TSpin::None, 0);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn calc_score_tspin() {
+        {
+            let expected = 400;
+            let actual = calc_score(0, TSpin::Full, 0);
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = 1600;
+            let actual = calc_score(3, TSpin::Full, 0);
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = 400;
+            let actual = calc_score(1, TSpin::Mini, 1);
+            assert_eq!(actual, expected);
+        }
+        {
+            let expected = 0;
+            let actual = calc_score(4, TSpin::Full, 0);
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn award_back_to_back() {
+        let rules = Guideline::default();
+        let mut scoring = Scoring::new();
+        rules.award(&mut scoring, lines(4));
+
+        let expected = vec![GameEvent::BackToBack, GameEvent::Combo(1)];
+        let actual = rules.award(&mut scoring, lines(4));
+        assert_eq!(actual, expected);
+
+        // 800, then 800 * 1.5 + 50 for the combo
+        let expected = 2050;
+        let actual = scoring.score;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn award_combo_break() {
+        let rules = Guideline::default();
+        let mut scoring = Scoring::new();
+        rules.award(&mut scoring, lines(4));
+        rules.award(&mut scoring, lines(0));
+
+        // a single breaks back to back, and the combo starts over
+        let expected: Vec<GameEvent> = vec![];
+        let actual = rules.award(&mut scoring, lines(1));
+        assert_eq!(actual, expected);
+        assert!(!scoring.back_to_back);
+    }
+
+    #[test]
+    fn award_all_clear() {
+        let rules = Guideline::default();
+        let mut scoring = Scoring::new();
+
+        let clear = Clear {
+            all_clear: true,
+            ..lines(2)
+        };
+        let expected = vec![GameEvent::AllClear(2)];
+        let actual = rules.award(&mut scoring, clear);
+        assert_eq!(actual, expected);
+
+        let expected = 300 + 1200;
+        let actual = scoring.score;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn variable_goal() {
+        let rules = Guideline {
+            goal: LevelGoal::Variable,
+        };
+        let mut scoring = Scoring::new();
+
+        // a tetris is worth 8 credits, and level 1 takes 5
+        rules.award(&mut scoring, lines(4));
+        let expected = (1, 3);
+        let actual = (scoring.level, scoring.progress);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn gravity_cap() {
+        let expected = crate::MAX_GRAVITY;
+        let actual = Guideline::default().gravity(30);
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,148 @@
+//! Scoring and leveling rules
+//!
+//! A rule set decides how many points a clear is worth, how the level advances, and how fast
+//! pieces fall at each level. The rule set is picked by the game mode, see `ScoringKind`.
+use serde::{Deserialize, Serialize};
+
+use crate::{component, GameEvent, GameState, TSpin, TetrsResult};
+mod guideline;
+mod nes;
+mod tgm;
+
+pub use guideline::{Guideline, LevelGoal};
+pub use nes::Nes;
+pub use tgm::{Grade, Tgm};
+
+/// A piece locking, and the lines it cleared
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Clear {
+    /// Number of lines cleared
+    pub lines: usize,
+    /// Kind of T-spin the piece locked with
+    pub tspin: TSpin,
+    /// True if the clear emptied the glass
+    pub all_clear: bool,
+}
+
+/// Rules for scoring and leveling
+pub trait ScoringRules {
+    /// Get the level a game starts on
+    fn start_level(&self) -> u32 {
+        0
+    }
+
+    /// Get the gravity, in 1/65536 G, for `level`
+    fn gravity(&self, level: u32) -> u32;
+
+    /// Award points for a piece locking, and advance the level. Returns events for each bonus
+    /// awarded
+    fn award(&self, scoring: &mut component::Scoring, clear: Clear) -> Vec<GameEvent>;
+
+    /// Get the points for a piece soft dropped `cells` rows
+    fn soft_drop_points(&self, cells: u32) -> u32 {
+        cells
+    }
+
+    /// Update the scoring state when a new piece spawns
+    fn on_spawn(&self, _scoring: &mut component::Scoring) {}
+
+    /// Update the scoring state once per unpaused frame
+    fn on_frame(&self, _scoring: &mut component::Scoring) {}
+
+    /// Get the level as shown to the player
+    fn display_level(&self, level: u32) -> u32 {
+        level + 1
+    }
+
+    /// Get the player's grade, if the rules grade players
+    fn grade(&self, _scoring: &component::Scoring) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Available scoring rule sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScoringKind {
+    /// Modern guideline scoring, with fixed or variable goal levels
+    Guideline { goal: LevelGoal },
+    /// NES scoring and gravity, starting on `start_level`
+    Nes { start_level: u32 },
+    /// TGM style levels and gravity, with an internal grade
+    Tgm,
+}
+
+impl Default for ScoringKind {
+    fn default() -> Self {
+        Self::Guideline {
+            goal: LevelGoal::default(),
+        }
+    }
+}
+
+impl ScoringKind {
+    /// Create scoring rules of this kind
+    pub fn create(self) -> Box<dyn ScoringRules> {
+        match self {
+            Self::Guideline { goal } => Box::new(Guideline { goal }),
+            Self::Nes { start_level } => Box::new(Nes::new(start_level)),
+            Self::Tgm => Box::new(Tgm),
+        }
+    }
+}
+
+/// Run `f` with the game's scoring rules and its Scoring resource
+fn with_scoring<F, T>(state: &mut GameState, f: F) -> TetrsResult<T>
+where
+    F: FnOnce(&dyn ScoringRules, &mut component::Scoring) -> T,
+{
+    let scoring = state
+        .ecs
+        .resources
+        .get_mut::<component::Scoring>()
+        .ok_or(crate::Error::MissingComponent("Scoring"))?;
+
+    Ok(f(state.scoring_rules.as_ref(), scoring))
+}
+
+/// Award points for a piece soft dropped `cells` rows
+pub fn add_soft_drop_points(state: &mut GameState, cells: u32) -> TetrsResult<()> {
+    with_scoring(state, |rules, scoring| {
+        scoring.score += rules.soft_drop_points(cells)
+    })
+}
+
+/// Update the game score based on `line_count` lines cleared by a piece that locked with `tspin`.
+///
+/// Checks the matrix for an all clear, so it has to run after the cleared lines are removed
+pub fn update_score(state: &mut GameState, line_count: usize, tspin: TSpin) -> TetrsResult<()> {
+    let all_clear = state.matrix().is_empty();
+    let clear = Clear {
+        lines: line_count,
+        tspin,
+        all_clear,
+    };
+
+    let (level, mut events) = with_scoring(state, |rules, scoring| {
+        let level = scoring.level;
+        (level, rules.award(scoring, clear))
+    })?;
+    let new_level = crate::get_level(state)?;
+    if new_level > level {
+        events.push(GameEvent::LevelUp(new_level));
+    }
+    for event in events {
+        state.ecs.events.send(event);
+    }
+
+    Ok(())
+}
+
+/// Update the scoring state for a newly spawned piece
+pub fn on_spawn(state: &mut GameState) -> TetrsResult<()> {
+    with_scoring(state, |rules, scoring| rules.on_spawn(scoring))
+}
+
+/// Update the scoring state for an unpaused frame
+pub fn on_frame(state: &mut GameState) -> TetrsResult<()> {
+    with_scoring(state, |rules, scoring| rules.on_frame(scoring))
+}
@@ -0,0 +1,110 @@
+//! NES scoring
+use super::{Clear, ScoringRules};
+use crate::{component::Scoring, GameEvent, G};
+
+/// NES scoring: no bonuses for T-spins, back-to-back or combos, NES gravity, and a choice of
+/// starting level
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Nes {
+    start_level: u32,
+    /// Lines it takes to leave the starting level
+    first_goal: u32,
+}
+
+/// Frames per row of fall for levels 0 to 28. Level 29 and up fall a row every frame
+const FRAMES_PER_ROW: [u32; 29] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+/// Points per line count, before the level multiplier
+const POINT_MAP: [u32; 5] = [0, 40, 100, 300, 1200];
+
+impl Nes {
+    /// Create a new instance, starting on `start_level`
+    pub fn new(start_level: u32) -> Self {
+        // starting higher takes more lines to leave the first level, as on the NES
+        let first_goal = std::cmp::min(
+            start_level * 10 + 10,
+            std::cmp::max(100, (start_level * 10).saturating_sub(50)),
+        );
+
+        Self {
+            start_level,
+            first_goal,
+        }
+    }
+
+    /// Get the level reached after clearing `lines` lines
+    fn level(&self, lines: u32) -> u32 {
+        if lines < self.first_goal {
+            self.start_level
+        } else {
+            self.start_level + 1 + (lines - self.first_goal) / 10
+        }
+    }
+}
+
+impl ScoringRules for Nes {
+    fn start_level(&self) -> u32 {
+        self.start_level
+    }
+
+    fn gravity(&self, level: u32) -> u32 {
+        let frames = FRAMES_PER_ROW.get(level as usize).copied().unwrap_or(1);
+        G.div_ceil(frames)
+    }
+
+    fn award(&self, scoring: &mut Scoring, clear: Clear) -> Vec<GameEvent> {
+        let points = POINT_MAP.get(clear.lines).copied().unwrap_or(0);
+        scoring.score += points * (scoring.level + 1);
+        scoring.lines += clear.lines as u32;
+        scoring.level = self.level(scoring.lines);
+
+        Vec::new()
+    }
+
+    fn display_level(&self, level: u32) -> u32 {
+        level
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn start_level_transition() {
+        {
+            // level 0 leaves after 10 lines
+            let expected = (0, 1);
+            let nes = Nes::new(0);
+            let actual = (nes.level(9), nes.level(10));
+            assert_eq!(actual, expected);
+        }
+        {
+            // level 18 leaves after 130 lines, then every 10 lines
+            let expected = (18, 19, 20);
+            let nes = Nes::new(18);
+            let actual = (nes.level(129), nes.level(130), nes.level(140));
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn award_tetris() {
+        let nes = Nes::new(9);
+        let mut scoring = Scoring {
+            level: 9,
+            ..Scoring::new()
+        };
+
+        let clear = Clear {
+            lines: 4,
+            ..Clear::default()
+        };
+        nes.award(&mut scoring, clear);
+        let expected = 12000;
+        let actual = scoring.score;
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,233 @@
+//! TGM style scoring, with an internal grade
+//!
+//! The level goes up by one for each piece spawned, and by one for each line cleared, up to 999.
+//! The level stops at the end of each section (x99, and 998) until a line is cleared.
+//!
+//! Line clears earn grade points. Every 100 points raises the internal grade, and points decay
+//! while the player isn't keeping a combo going. The internal grade maps to the displayed grade,
+//! 9 up to S9.
+use serde::{Deserialize, Serialize};
+
+use super::{Clear, ScoringRules};
+use crate::{component::Scoring, GameEvent};
+
+/// Highest level
+const MAX_LEVEL: u32 = 999;
+
+/// Gravity, in 1/256 G, from each level on
+const GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+/// Displayed grade for each internal grade
+const GRADE_NAMES: [&str; 32] = [
+    "9", "8", "7", "6", "5", "4", "4", "3", "3", "2", "2", "2", "1", "1", "1", "S1", "S1", "S1",
+    "S2", "S3", "S4", "S4", "S4", "S5", "S5", "S6", "S6", "S7", "S7", "S8", "S8", "S9",
+];
+
+/// Frames per lost grade point, for each internal grade
+const DECAY: [u32; 32] = [
+    125, 80, 80, 50, 45, 45, 45, 40, 40, 40, 40, 40, 30, 30, 30, 20, 20, 20, 20, 20, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 10, 10,
+];
+
+/// Grade points for 1 to 4 lines, by internal grade. Grades past the table use the last row
+const GRADE_POINTS: [(u32, [u32; 4]); 5] = [
+    (0, [10, 20, 40, 50]),
+    (1, [10, 20, 30, 40]),
+    (3, [10, 15, 30, 40]),
+    (5, [5, 10, 20, 30]),
+    (10, [2, 12, 13, 30]),
+];
+
+/// Grade points it takes to go up a grade
+const POINTS_PER_GRADE: u32 = 100;
+
+/// Internal grade progress
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grade {
+    /// Internal grade, an index into the displayed grades
+    pub internal: u32,
+    /// Points toward the next grade
+    pub points: u32,
+    /// Frames since the last point decayed
+    pub decay: u32,
+}
+
+impl Grade {
+    /// Get the displayed name of the grade
+    pub fn name(&self) -> &'static str {
+        GRADE_NAMES[self.internal as usize]
+    }
+
+    /// Earn points for clearing `lines` lines at `level`
+    fn add_points(&mut self, lines: usize, level: u32) {
+        if lines == 0 {
+            return;
+        }
+
+        let points = GRADE_POINTS
+            .iter()
+            .rev()
+            .find(|(grade, _)| self.internal >= *grade)
+            .map_or(0, |(_, points)| points[std::cmp::min(lines, 4) - 1]);
+        // clears are worth more later in the game
+        self.points += points * (1 + level / 250);
+
+        let last = GRADE_NAMES.len() as u32 - 1;
+        while self.points >= POINTS_PER_GRADE && self.internal < last {
+            self.points -= POINTS_PER_GRADE;
+            self.internal += 1;
+        }
+    }
+
+    /// Advance the decay timer by a frame
+    fn decay(&mut self) {
+        self.decay += 1;
+        if self.decay >= DECAY[self.internal as usize] {
+            self.decay = 0;
+            self.points = self.points.saturating_sub(1);
+        }
+    }
+}
+
+/// TGM scoring rules
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Tgm;
+
+impl ScoringRules for Tgm {
+    fn gravity(&self, level: u32) -> u32 {
+        let per_256 = GRAVITY
+            .iter()
+            .rev()
+            .find(|(from, _)| level >= *from)
+            .map_or(4, |(_, g)| *g);
+        per_256 * (crate::G / 256)
+    }
+
+    fn award(&self, scoring: &mut Scoring, clear: Clear) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        if clear.lines == 0 {
+            scoring.combo = None;
+            return events;
+        }
+
+        let lines = clear.lines as u32;
+        let combo = scoring.combo.unwrap_or(1) + 2 * lines - 2;
+        let bravo = if clear.all_clear {
+            events.push(GameEvent::AllClear(clear.lines));
+            4
+        } else {
+            1
+        };
+        scoring.score += (scoring.level + lines).div_ceil(4) * lines * combo * bravo;
+        scoring.combo = Some(combo);
+
+        scoring.grade.add_points(clear.lines, scoring.level);
+        scoring.lines += lines;
+        scoring.level = std::cmp::min(MAX_LEVEL, scoring.level + lines);
+
+        events
+    }
+
+    fn soft_drop_points(&self, _cells: u32) -> u32 {
+        0
+    }
+
+    fn on_spawn(&self, scoring: &mut Scoring) {
+        // the level stops at the end of a section until a line is cleared
+        if scoring.level % 100 != 99 && scoring.level < MAX_LEVEL - 1 {
+            scoring.level += 1;
+        }
+    }
+
+    fn on_frame(&self, scoring: &mut Scoring) {
+        // points only decay while there's no combo going
+        if scoring.combo.is_none() {
+            scoring.grade.decay();
+        }
+    }
+
+    fn display_level(&self, level: u32) -> u32 {
+        level
+    }
+
+    fn grade(&self, scoring: &Scoring) -> Option<&'static str> {
+        Some(scoring.grade.name())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn level_stop() {
+        let mut scoring = Scoring {
+            level: 98,
+            ..Scoring::new()
+        };
+        Tgm.on_spawn(&mut scoring);
+        Tgm.on_spawn(&mut scoring);
+
+        let expected = 99;
+        let actual = scoring.level;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn award_combo() {
+        let mut scoring = Scoring::new();
+        let clear = Clear {
+            lines: 2,
+            ..Clear::default()
+        };
+        Tgm.award(&mut scoring, clear);
+        Tgm.award(&mut scoring, clear);
+
+        // ceil(2 / 4) * 2 * 3, then ceil(4 / 4) * 2 * 5
+        let expected = 6 + 10;
+        let actual = scoring.score;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn grade_up() {
+        let mut grade = Grade::default();
+        grade.add_points(4, 0);
+        grade.add_points(4, 0);
+
+        let expected = ("8", 0);
+        let actual = (grade.name(), grade.points);
+        assert_eq!(actual, expected);
+    }
+}
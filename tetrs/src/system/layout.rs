@@ -0,0 +1,58 @@
+//! Screen layout, derived from the glass size
+use crate::{component::Position, GameState};
+
+/// Columns left of the glass, for the score and hold piece
+const LEFT_PANEL: i32 = 12;
+/// Columns right of the glass, for the previews
+const RIGHT_PANEL: i32 = 14;
+/// Rows above the glass
+const TOP_MARGIN: i32 = 3;
+/// Rows below the glass
+const BOTTOM_MARGIN: i32 = 4;
+
+/// Where each part of the game is drawn, in cells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    /// Top left cell of the visible glass
+    pub glass: Position,
+    /// Top left cell of the first preview
+    pub preview: Position,
+    /// Top left cell of the hold piece
+    pub hold: Position,
+    /// Width of the whole screen
+    pub columns: u32,
+    /// Height of the whole screen
+    pub rows: u32,
+}
+
+impl Layout {
+    /// Create the layout for `state`'s glass
+    pub fn new(state: &GameState) -> Self {
+        let width = state.width() as i32;
+        let height = state.height() as i32;
+        let glass = Position {
+            x: LEFT_PANEL,
+            y: TOP_MARGIN,
+        };
+
+        Self {
+            glass,
+            preview: Position {
+                x: glass.x + width + 3,
+                y: glass.y,
+            },
+            hold: Position {
+                x: glass.x / 2,
+                y: glass.y * 3,
+            },
+            columns: (LEFT_PANEL + width + RIGHT_PANEL) as u32,
+            rows: (TOP_MARGIN + height + BOTTOM_MARGIN) as u32,
+        }
+    }
+
+    /// Get the largest cell width, in pixels, that fits the layout in a `width` by `height`
+    /// canvas
+    pub fn cell_width(&self, (width, height): (u32, u32)) -> u32 {
+        std::cmp::min(width / self.columns, height / self.rows)
+    }
+}
@@ -1,67 +1,318 @@
-use crate::{component::*, Error, GameState};
+use crate::{component::*, matrix::Matrix, schedule::stage, Config, Error, GameState, Phase};
 use cell_engine::gfx;
-use ecs::ComponentRegistry;
+use ecs::{types::Cell, Access, ComponentRegistry, Schedule, System, Without};
 use sdl2::{rect::Rect, render::Canvas, ttf, video::Window};
 
+mod layout;
+
+pub use layout::Layout;
+
+/// A game and what it's drawn with, for the render systems
+pub struct Screen<'a> {
+    pub state: GameState,
+    pub canvas: &'a mut Canvas<Window>,
+    pub font: &'a ttf::Font<'a, 'a>,
+    pub cell_width: u32,
+}
+
+/// A render system: one of the draw functions
+struct Draw {
+    name: &'static str,
+    after: &'static [&'static str],
+    access: fn() -> Access,
+    draw: for<'a> fn(&mut Screen<'a>) -> Result<(), Error>,
+}
+
+impl<'a> System<Screen<'a>, Error> for Draw {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn access(&self) -> Access {
+        (self.access)()
+    }
+
+    fn after(&self) -> Vec<&'static str> {
+        self.after.to_vec()
+    }
+
+    fn run(&mut self, screen: &mut Screen<'a>) -> Result<(), Error> {
+        (self.draw)(screen)
+    }
+}
+
+/// Create the schedule that draws a frame. Everything is drawn over the background
+pub fn render_schedule<'a>() -> Schedule<Screen<'a>, Error> {
+    // everything is placed by the layout, which reads the glass size from the config
+    let systems = [
+        Draw {
+            name: "background",
+            after: &[],
+            access: || Access::new().read::<Config>(),
+            draw: |s| draw_ui_bg(&s.state, s.canvas, s.cell_width),
+        },
+        Draw {
+            name: "ui",
+            after: &["background"],
+            access: || Access::new().read::<Config>().read::<Scoring>(),
+            draw: |s| draw_ui(&mut s.state, s.canvas, s.cell_width, s.font),
+        },
+        Draw {
+            name: "entities",
+            after: &["background"],
+            access: || {
+                Access::new()
+                    .read::<Config>()
+                    .read::<Cell>()
+                    .read::<Mesh>()
+                    .read::<Position>()
+                    .read::<Preview>()
+                    .read::<Hold>()
+            },
+            draw: |s| draw_entities(&mut s.state, s.canvas, s.cell_width),
+        },
+        Draw {
+            name: "line_clear",
+            after: &["entities"],
+            access: || Access::new().read::<Config>().read::<Phase>(),
+            draw: |s| draw_line_clear(&s.state, s.canvas, s.cell_width),
+        },
+        Draw {
+            name: "ghost",
+            after: &["line_clear"],
+            access: || {
+                Access::new()
+                    .read::<Config>()
+                    .read::<CurrentPiece>()
+                    .read::<Cell>()
+                    .read::<Mesh>()
+                    .read::<Position>()
+                    .read::<Matrix>()
+            },
+            draw: |s| draw_ghost(&mut s.state, s.canvas, s.cell_width),
+        },
+        Draw {
+            name: "game_over",
+            after: &["ghost", "ui"],
+            access: || Access::new().read::<Config>().read::<Status>(),
+            draw: |s| draw_game_over(&s.state, s.canvas, s.cell_width, s.font),
+        },
+        Draw {
+            name: "previews",
+            after: &["background"],
+            access: || {
+                Access::new()
+                    .read::<Config>()
+                    .read::<PieceQueue>()
+                    .read::<Cell>()
+                    .read::<Mesh>()
+                    .read::<Preview>()
+            },
+            draw: |s| draw_previews(&mut s.state, s.canvas, s.cell_width),
+        },
+        Draw {
+            name: "hold",
+            after: &["background"],
+            access: || {
+                Access::new()
+                    .read::<Config>()
+                    .read::<HoldPiece>()
+                    .read::<PieceState>()
+                    .read::<Cell>()
+                    .read::<Mesh>()
+                    .read::<Hold>()
+            },
+            draw: |s| draw_hold(&mut s.state, s.canvas, s.cell_width),
+        },
+    ];
+
+    let mut schedule = Schedule::new();
+    schedule.add_stage(stage::RENDER);
+    for system in systems {
+        schedule.add_system(stage::RENDER, system);
+    }
+    schedule
+}
+
 /// Draw entities on the gamefield
 pub fn draw_entities(
     state: &mut GameState,
     canvas: &mut Canvas<Window>,
     cell_width: u32,
 ) -> Result<(), Error> {
-    let ecs = &mut state.ecs;
-    let registry = ecs.component_registry.lock().unwrap();
-
-    for e in ecs.entity_list.iter() {
-        if !ecs.entity_manager.alive(e) {
-            continue;
-        }
-
-        let color = registry.get_component::<ColorComponent, _>(&e);
-        let mesh = registry.get_component::<MeshComponent, _>(&e);
-        let pos = registry.get_component::<PositionComponent, _>(&e);
-        let preview = registry.get_component::<PreviewComponent, _>(&e);
-        let hold = registry.get_component::<HoldComponent, _>(&e);
-        // aspect check - if a component is missing, do nothing
-        if preview.is_some() || hold.is_some() || color.is_none() || mesh.is_none() || pos.is_none()
-        {
-            continue;
-        }
+    let hidden_rows = state.config().hidden_rows as i32;
+    let layout = Layout::new(state);
+    let pieces = state
+        .ecs
+        .query_filtered::<(&Cell, &Mesh, &Position), (Without<Preview>, Without<Hold>)>();
 
-        let mesh = &mesh.unwrap().0;
-        let color = color.unwrap();
-        let pos = pos.unwrap();
+    for (color, Mesh(mesh), pos) in pieces {
         for Position { x, y } in mesh.iter() {
-            let y = *y + pos.y;
+            let y = *y + pos.y - hidden_rows;
             let x = *x + pos.x;
+            // only the visible glass is drawn
+            if y < 0 {
+                continue;
+            }
             gfx::display_cell_bordered(
                 canvas,
-                (y + crate::Y_OFFSET) as u32,
-                (x + crate::X_OFFSET) as u32,
-                *color,
+                (y + layout.glass.y) as u32,
+                (x + layout.glass.x) as u32,
+                (*color).into(),
                 cell_width,
                 1,
             );
-            // collision cache
-            state.grid[y as usize][x as usize] = *color;
         }
     }
 
     Ok(())
 }
 
+/// Outline the landing position of the current piece on the gamefield
+pub fn draw_ghost(
+    state: &mut GameState,
+    canvas: &mut Canvas<Window>,
+    cell_width: u32,
+) -> Result<(), Error> {
+    if !state.config().ghost {
+        return Ok(());
+    }
+    let e = match state.current_piece() {
+        Some(e) if state.ecs.entity_manager.alive(&e) => e,
+        _ => return Ok(()),
+    };
+
+    let ghost_pos = crate::input::ghost_position(state, e)?;
+    let layout = Layout::new(state);
+    let registry = &state.ecs.component_registry;
+    let color = registry.get_component::<ColorComponent, _>(&e);
+    let mesh = registry.get_component::<MeshComponent, _>(&e);
+    let pos = registry.get_component::<PositionComponent, _>(&e);
+    // aspect check - if a component is missing, do nothing
+    if color.is_none() || mesh.is_none() || pos.is_none() {
+        return Ok(());
+    }
+
+    let mesh = &mesh.unwrap().0;
+    let color = color.unwrap();
+    let pos = pos.unwrap();
+    for cell in mesh.iter() {
+        // don't draw over the piece itself
+        if mesh.contains(&(*cell + ghost_pos - *pos)) {
+            continue;
+        }
+
+        let Position { x, y } = *cell + ghost_pos;
+        let y = y - state.config().hidden_rows as i32;
+        if y < 0 {
+            continue;
+        }
+        gfx::display_cell_outline(
+            canvas,
+            (y + layout.glass.y) as u32,
+            (x + layout.glass.x) as u32,
+            (*color).into(),
+            cell_width,
+            2,
+        );
+    }
+
+    Ok(())
+}
+
+/// Frames per flash while lines are being cleared
+const FLASH_FRAMES: u32 = 4;
+
+/// Animate the lines being cleared: they flash for the first half of the line clear delay, then
+/// empty from the middle out
+pub fn draw_line_clear(
+    state: &GameState,
+    canvas: &mut Canvas<Window>,
+    cell_width: u32,
+) -> Result<(), Error> {
+    let (lines, frame) = match state.phase() {
+        Phase::LineClear { lines, frame, .. } => (lines, *frame),
+        _ => return Ok(()),
+    };
+
+    let layout = Layout::new(state);
+    let width = state.width() as u32;
+    let delay = std::cmp::max(1, state.config().entry_delay.line_clear);
+    let flash = delay / 2;
+    // columns emptied on each side of the middle
+    let gone = match frame.checked_sub(flash) {
+        Some(collapse) => (collapse + 1) * width.div_ceil(2) / (delay - flash),
+        None => 0,
+    };
+
+    for line in lines {
+        let y = *line as i32 - state.config().hidden_rows as i32;
+        if y < 0 {
+            continue;
+        }
+        let y = (y + layout.glass.y) as u32;
+
+        for x in 0..width {
+            let col = (x as i32 + layout.glass.x) as u32;
+            if frame < flash {
+                if (frame / FLASH_FRAMES).is_multiple_of(2) {
+                    gfx::display_cell_bordered(
+                        canvas,
+                        y,
+                        col,
+                        crate::WHITISH.into(),
+                        cell_width,
+                        1,
+                    );
+                }
+            } else if (2 * x + 1).abs_diff(width) < 2 * gone {
+                // distance from the middle, in half cells
+                gfx::display_cell(canvas, y, col, crate::BG_CELL.into(), cell_width);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write "GAME OVER" across the glass once the game has ended
+pub fn draw_game_over(
+    state: &GameState,
+    canvas: &mut Canvas<Window>,
+    cell_width: u32,
+    font: &ttf::Font,
+) -> Result<(), Error> {
+    if !state.status().game_over {
+        return Ok(());
+    }
+
+    // centered on the visible glass
+    let layout = Layout::new(state);
+    let cell_width = cell_width as i32;
+    let x = layout.glass.x * cell_width + state.width() as i32 * cell_width / 2;
+    let y = layout.glass.y * cell_width + state.height() as i32 * cell_width / 2;
+    gfx::render_text(font, canvas, crate::PINK.into(), (x, y), "GAME OVER");
+
+    Ok(())
+}
+
 /// Draw the UI background
-pub fn draw_ui_bg(renderer: &mut Canvas<Window>, cell_width: u32) -> Result<(), Error> {
-    gfx::clear_frame(renderer, crate::UI_BG);
+pub fn draw_ui_bg(
+    state: &GameState,
+    renderer: &mut Canvas<Window>,
+    cell_width: u32,
+) -> Result<(), Error> {
+    gfx::clear_frame(renderer, crate::UI_BG.into());
 
+    // only the visible rows of the glass
+    let layout = Layout::new(state);
     renderer.set_draw_color(crate::BG_CELL);
-    renderer
-            .fill_rect(Rect::new(
-                    (cell_width * crate::X_OFFSET as u32) as i32,
-                    (cell_width * crate::Y_OFFSET as u32) as i32,
-                    cell_width * crate::GLASS_WIDTH as u32,
-                    cell_width * crate::GLASS_HEIGHT as u32,
-                    ))?;
+    renderer.fill_rect(Rect::new(
+        cell_width as i32 * layout.glass.x,
+        cell_width as i32 * layout.glass.y,
+        cell_width * state.width() as u32,
+        cell_width * state.height() as u32,
+    ))?;
 
     Ok(())
 }
@@ -72,13 +323,13 @@ pub fn draw_previews(
     canvas: &mut Canvas<Window>,
     cell_width: u32,
 ) -> Result<(), Error> {
+    let layout = Layout::new(state);
     let ecs = &mut state.ecs;
-    let registry = ecs.component_registry.lock().unwrap();
+    let registry = &ecs.component_registry;
 
-    const PREVIEW_X: i32 = 3 + crate::X_OFFSET + crate::GLASS_WIDTH as i32;
-    let mut y_offset = crate::Y_OFFSET;
+    let mut y_offset = layout.preview.y;
 
-    for e in state.next_pieces.iter() {
+    for e in ecs.resource::<PieceQueue>().0.iter() {
         if !ecs.entity_manager.alive(e) {
             continue;
         }
@@ -94,9 +345,9 @@ pub fn draw_previews(
         let mesh = &mesh.unwrap().0;
         let color = color.unwrap();
         for Position { x, y } in mesh.iter() {
-            let x = (x + PREVIEW_X) as u32;
+            let x = (x + layout.preview.x) as u32;
             let y = (y + y_offset) as u32;
-            gfx::display_cell_bordered(canvas, y, x, *color, cell_width, 1);
+            gfx::display_cell_bordered(canvas, y, x, (*color).into(), cell_width, 1);
         }
 
         y_offset += 3;
@@ -111,13 +362,16 @@ pub fn draw_hold(
     canvas: &mut Canvas<Window>,
     cell_width: u32,
 ) -> Result<(), Error> {
+    let layout = Layout::new(state);
+    let hold_piece = state.hold_piece();
+    let hold_used = state.piece_state().hold_used;
     let ecs = &mut state.ecs;
-    let registry = ecs.component_registry.lock().unwrap();
+    let registry = &ecs.component_registry;
 
-    let x_offset: i32 = (crate::X_OFFSET) / 2;
-    let y_offset = crate::Y_OFFSET * 3;
+    let x_offset = layout.hold.x;
+    let y_offset = layout.hold.y;
 
-    let e = state.hold_piece.and_then(|e| {
+    let e = hold_piece.and_then(|e| {
         if ecs.entity_manager.alive(&e) {
             Some(e)
         } else {
@@ -139,11 +393,15 @@ pub fn draw_hold(
     }
 
     let mesh = &mesh.unwrap().0;
-    let color = color.unwrap();
+    // greyed out until the next piece locks
+    let color = match hold_used {
+        true => crate::HOLD_LOCKED,
+        false => *color.unwrap(),
+    };
     for Position { x, y } in mesh.iter() {
         let x = (x + x_offset) as u32;
         let y = (y + y_offset) as u32;
-        gfx::display_cell_bordered(canvas, y, x, *color, cell_width, 1);
+        gfx::display_cell_bordered(canvas, y, x, color.into(), cell_width, 1);
     }
 
     Ok(())
@@ -158,21 +416,34 @@ pub fn draw_ui(
 ) -> Result<(), Error> {
     let v_step = font.height() as i32;
     let line_txt = &format!("Lines: {}", crate::get_lines(ecs).unwrap());
-    let level_txt = &format!("Level: {}", crate::get_level(ecs).unwrap() + 1);
+    let level = ecs
+        .scoring_rules
+        .display_level(crate::get_level(ecs).unwrap());
+    let level_txt = &format!("Level: {}", level);
     let score_txt = &format!("Score: {}", crate::get_score(ecs).unwrap());
 
-    let x = (crate::X_OFFSET * cell_width as i32) / 2;
-    let mut y = crate::Y_OFFSET * cell_width as i32 + (font.height() / 2);
-    gfx::render_text(font, renderer, crate::PINK, (x, y), level_txt);
+    let layout = Layout::new(ecs);
+    let x = layout.hold.x * cell_width as i32;
+    let mut y = layout.glass.y * cell_width as i32 + (font.height() / 2);
+    gfx::render_text(font, renderer, crate::PINK.into(), (x, y), level_txt);
 
     y += v_step;
-    gfx::render_text(font, renderer, crate::BLUE, (x, y), line_txt);
+    gfx::render_text(font, renderer, crate::BLUE.into(), (x, y), line_txt);
 
     y += v_step;
-    gfx::render_text(font, renderer, crate::TEXT_COLOR, (x, y), score_txt);
+    gfx::render_text(font, renderer, crate::TEXT_COLOR.into(), (x, y), score_txt);
 
     y += v_step;
-    gfx::render_text(font, renderer, crate::PINK, (x, y), "Hold Piece: ");
+    if ecs.config().hold {
+        gfx::render_text(font, renderer, crate::PINK.into(), (x, y), "Hold Piece: ");
+    }
+
+    // below the hold piece
+    if let Some(grade) = crate::get_grade(ecs).unwrap() {
+        y += v_step + 4 * cell_width as i32;
+        let grade_txt = &format!("Grade: {}", grade);
+        gfx::render_text(font, renderer, crate::PINK.into(), (x, y), grade_txt);
+    }
 
     Ok(())
 }
@@ -2,6 +2,7 @@ use std::collections::VecDeque;
 
 use ecs::{self, types};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 const O_COLOR: types::Cell = types::Cell::RGB(0, 0, 255);
 const I_COLOR: types::Cell = types::Cell::RGB(255, 0, 0);
@@ -12,6 +13,7 @@ const S_COLOR: types::Cell = types::Cell::RGB(0, 255, 0);
 const T_COLOR: types::Cell = types::Cell::RGB(255, 255, 0);
 
 /// one-sided tetromino types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Tetromino {
     O,
     I,
@@ -23,6 +25,17 @@ pub enum Tetromino {
 }
 
 impl Tetromino {
+    /// Every variant of Tetromino
+    pub const ALL: [Tetromino; 7] = [
+        Tetromino::O,
+        Tetromino::I,
+        Tetromino::J,
+        Tetromino::L,
+        Tetromino::Z,
+        Tetromino::S,
+        Tetromino::T,
+    ];
+
     /// Create a mesh component for this type of Tetromino
     pub fn new(&self) -> crate::component::Mesh {
         match self {
@@ -62,18 +75,9 @@ impl Tetromino {
         }
     }
 
-    /// Get a random variant of Tetromino
-    pub fn random() -> Self {
-        let row = rand::thread_rng().gen_range(0, 7);
-        match row {
-            row if row == 0 => Tetromino::O,
-            row if row == 1 => Tetromino::I,
-            row if row == 2 => Tetromino::J,
-            row if row == 3 => Tetromino::L,
-            row if row == 4 => Tetromino::Z,
-            row if row == 5 => Tetromino::S,
-            _ => Tetromino::T,
-        }
+    /// Get a random variant of Tetromino, drawn uniformly from `rng`
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::ALL[rng.gen_range(0, Self::ALL.len())]
     }
 }
 
@@ -0,0 +1,124 @@
+//! T-spin detection
+//!
+//! A T piece that locks right after a rotation is a T-spin if three of the four cells diagonal
+//! to its center are blocked. It's a full T-spin if both corners on the pointing side are
+//! blocked, otherwise a mini. Walls and the floor count as blocked.
+use serde::{Deserialize, Serialize};
+
+use crate::{component, Error, GameState, Tetromino, TetrsResult};
+use ecs::{types::Entity, ComponentRegistry};
+type Position = component::Position;
+
+/// Kind of T-spin a piece locked with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TSpin {
+    /// Not a T-spin
+    #[default]
+    None,
+    Mini,
+    Full,
+}
+
+/// Cells diagonal to a T's center
+const CORNERS: [Position; 4] = [
+    Position { x: -1, y: -1 },
+    Position { x: 1, y: -1 },
+    Position { x: -1, y: 1 },
+    Position { x: 1, y: 1 },
+];
+
+/// Find the center cell of a T mesh and the direction the T points in. The center is the cell
+/// touching the other three, and the T points toward the neighbor with no cell opposite it
+fn t_center(mesh: &component::Mesh) -> Option<(Position, Position)> {
+    const NEIGHBORS: [Position; 4] = [
+        Position { x: 0, y: -1 },
+        Position { x: 1, y: 0 },
+        Position { x: 0, y: 1 },
+        Position { x: -1, y: 0 },
+    ];
+
+    let center = *mesh.0.iter().find(|cell| {
+        NEIGHBORS
+            .iter()
+            .filter(|dir| mesh.0.contains(&(**cell + **dir)))
+            .count()
+            == 3
+    })?;
+    let facing = *NEIGHBORS
+        .iter()
+        .find(|dir| mesh.0.contains(&(center + **dir)) && !mesh.0.contains(&(center - **dir)))?;
+
+    Some((center, facing))
+}
+
+/// True if the last kick moved the piece one column and two rows, which upgrades a mini to a
+/// full T-spin
+fn is_far_kick(kick: Position) -> bool {
+    kick.x.abs() == 1 && kick.y.abs() == 2
+}
+
+/// Check if the piece `e`, about to lock, is a T-spin. The matrix must not include the piece
+pub fn check_tspin(state: &GameState, e: Entity) -> TetrsResult<TSpin> {
+    let kick = match state.piece_state().last_rotation {
+        Some(kick) => kick,
+        None => return Ok(TSpin::None),
+    };
+
+    let registry = &state.ecs.component_registry;
+    let kind = registry
+        .get_component::<component::KindComponent, _>(&e)
+        .ok_or(Error::MissingComponent("Kind"))?;
+    if *kind != Tetromino::T {
+        return Ok(TSpin::None);
+    }
+    let mesh = registry
+        .get_component::<component::MeshComponent, _>(&e)
+        .ok_or(Error::MissingComponent("Mesh"))?;
+    let pos = *registry
+        .get_component::<component::PositionComponent, _>(&e)
+        .ok_or(Error::MissingComponent("Position"))?;
+
+    let (center, facing) = match t_center(mesh) {
+        Some(center) => center,
+        None => return Ok(TSpin::None),
+    };
+    let blocked = |corner: Position| state.matrix().blocked(pos + center + corner);
+
+    let corners = CORNERS.iter().filter(|corner| blocked(**corner)).count();
+    if corners < 3 {
+        return Ok(TSpin::None);
+    }
+
+    let side = Position {
+        x: facing.y.abs(),
+        y: facing.x.abs(),
+    };
+    if (blocked(facing + side) && blocked(facing - side)) || is_far_kick(kick) {
+        return Ok(TSpin::Full);
+    }
+
+    Ok(TSpin::Mini)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RotationState;
+
+    #[test]
+    fn t_center_spawn() {
+        let expected = Some((Position { x: 1, y: 1 }, Position { x: 0, y: -1 }));
+        let actual = t_center(&Tetromino::T.new());
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn t_center_right() {
+        let mesh = crate::rotation::RotationKind::SRS
+            .system()
+            .mesh(Tetromino::T, RotationState::Right);
+        let expected = Some((Position { x: 1, y: 1 }, Position { x: 1, y: 0 }));
+        let actual = t_center(&mesh);
+        assert_eq!(actual, expected);
+    }
+}
@@ -11,8 +11,10 @@ pub enum InputEvent {
     HardDrop,
     Hold,
     Left,
+    LeftEnd,
     Pause,
     Right,
+    RightEnd,
     RotateCCW,
     RotateCW,
     SoftDrop,
@@ -26,25 +28,38 @@ pub enum KeyState {
     Down,
 }
 
-/// Events generated by game rules
+/// Why a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOverReason {
+    /// A new piece spawned overlapping the stack
+    BlockOut,
+    /// A piece locked entirely above the visible glass
+    LockOut,
+    /// After a piece locked and lines cleared, the stack reached the top row of the buffer
+    TopOut,
+}
+
+/// Events generated by game rules. Sent on the world's `GameEvent` channel
 #[derive(Debug, Clone, PartialEq)]
 pub enum GameEvent {
-    GameOver,
+    GameOver(GameOverReason),
     Collision(ecs::types::Entity),
+    /// A piece locked into the stack
+    Locked(crate::Tetromino),
+    /// The current piece was held
+    Hold(crate::Tetromino),
     ClearedLines(Vec<usize>),
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum TetrsEvent {
-    InputEvent,
-    GameEvent,
-}
-
-/// Events generated by or controlling a Timer
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum TimerEvent {
-    Tick,
-    Pause,
-    Stop,
-    SetInterval(u128),
+    /// A piece locked with a T-spin, clearing `lines` lines
+    TSpin {
+        kind: crate::TSpin,
+        lines: usize,
+    },
+    /// A tetris or T-spin clear followed another
+    BackToBack,
+    /// Another piece in a row cleared lines
+    Combo(u32),
+    /// A line clear emptied the glass
+    AllClear(usize),
+    /// The level went up to this level
+    LevelUp(u32),
 }